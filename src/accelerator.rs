@@ -0,0 +1,63 @@
+use std::ops::{BitOr, BitOrAssign};
+
+/// A single keyboard shortcut of an accelerator table.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Accelerator {
+    /// Either a virtual-key code (if [`AccelFlags::VIRTKEY`] is set) or an ASCII character code.
+    pub key: u16,
+    /// The command id that is sent with the `WM_COMMAND` message.
+    pub cmd: u16,
+    pub flags: AccelFlags
+}
+
+impl Accelerator {
+    pub fn new(key: u16, cmd: u16, flags: AccelFlags) -> Self {
+        Self { key, cmd, flags }
+    }
+}
+
+/// The modifier flags of an [`Accelerator`].
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccelFlags(u16);
+
+impl AccelFlags {
+    /// No modifiers, `key` is an ASCII character code.
+    pub const NONE: Self = Self(0x00);
+    /// `key` is a virtual-key code.
+    pub const VIRTKEY: Self = Self(0x01);
+    /// No top-level menu item is highlighted when the accelerator is used.
+    pub const NOINVERT: Self = Self(0x02);
+    /// The SHIFT key must be held down.
+    pub const SHIFT: Self = Self(0x04);
+    /// The CTRL key must be held down.
+    pub const CONTROL: Self = Self(0x08);
+    /// The ALT key must be held down.
+    pub const ALT: Self = Self(0x10);
+
+    /// Marks the last entry of an accelerator table. Set automatically when writing the table.
+    pub(crate) const LAST: u16 = 0x80;
+
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for AccelFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for AccelFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
@@ -1,5 +1,7 @@
+use crate::accelerator::{AccelFlags, Accelerator};
 use crate::binary::version::{FieldType, FieldValue};
-use crate::{Icon, IconGroupEntry, Version, VersionInfo};
+use crate::dialog::{DialogControl, DialogTemplate};
+use crate::{Icon, IconGroupEntry, LanguageId, Version, VersionInfo};
 
 pub trait BinaryWriter {
     fn pos(&self) -> usize;
@@ -24,6 +26,13 @@ pub trait BinaryWriter {
         let required_padding = (i - (self.pos() % i)) % i;
         self.reserve(required_padding)
     }
+
+    fn write_utf16(&mut self, text: &str) {
+        for c in text.encode_utf16() {
+            self.write_u16(c);
+        }
+        self.write_u16(0x0);
+    }
 }
 
 pub trait BinaryWritable {
@@ -56,12 +65,15 @@ impl BinaryWritable for [IconGroupEntry] {
         w.write_u16(self.len().try_into().expect("Too many icons in group")); // idCount
 
         for entry in self {
-            w.write_u8(0x0); // bWidth
-            w.write_u8(0x0); // bHeight
-            w.write_u8(0x0); // bColorCount
+            // a dimension of 0 means 256 pixels (or more)
+            w.write_u8(entry.width.try_into().unwrap_or(0)); // bWidth
+            w.write_u8(entry.height.try_into().unwrap_or(0)); // bHeight
+
+            // only images with less than 256 colors have a palette size that fits
+            w.write_u8(if entry.bit_count < 8 { 1 << entry.bit_count } else { 0 }); // bColorCount
             w.write_u8(0x0); // bReserved
             w.write_u16(0x1); // wPlanes
-            w.write_u16(32); // wBitCount
+            w.write_u16(entry.bit_count); // wBitCount
             w.write_u32(entry.icon_size.try_into().expect("icon file too large")); // dwBytesInRes
             w.write_u16(entry.icon_id);
         }
@@ -70,7 +82,76 @@ impl BinaryWritable for [IconGroupEntry] {
 
 impl BinaryWritable for Icon {
     fn write_to<W: BinaryWriter>(&self, w: &mut W) {
-        w.write_bytes(&self.0)
+        w.write_bytes(&self.data)
+    }
+}
+
+impl BinaryWritable for DialogTemplate {
+    fn write_to<W: BinaryWriter>(&self, w: &mut W) {
+        // https://learn.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-dlgtemplate
+        let style = match self.font {
+            Some(_) => self.style | DialogTemplate::DS_SETFONT,
+            None => self.style & !DialogTemplate::DS_SETFONT
+        };
+        w.write_u32(style);
+        w.write_u32(self.extended_style);
+        w.write_u16(
+            self.controls
+                .len()
+                .try_into()
+                .expect("Too many dialog controls")
+        ); // cdit
+        w.write_u16(self.x as u16);
+        w.write_u16(self.y as u16);
+        w.write_u16(self.width as u16);
+        w.write_u16(self.height as u16);
+        w.write_u16(0x0); // no menu
+        w.write_u16(0x0); // predefined dialog box class
+        w.write_utf16(&self.title);
+        if let Some(font) = &self.font {
+            w.write_u16(font.point_size);
+            w.write_utf16(&font.name);
+        }
+        for control in &self.controls {
+            // every item template starts on a DWORD boundary
+            w.align_to(4);
+            control.write_to(w);
+        }
+    }
+}
+
+impl BinaryWritable for DialogControl {
+    fn write_to<W: BinaryWriter>(&self, w: &mut W) {
+        // https://learn.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-dlgitemtemplate
+        w.write_u32(self.style);
+        w.write_u32(self.extended_style);
+        w.write_u16(self.x as u16);
+        w.write_u16(self.y as u16);
+        w.write_u16(self.width as u16);
+        w.write_u16(self.height as u16);
+        w.write_u16(self.id);
+        match self.class.ordinal() {
+            Some(ordinal) => {
+                w.write_u16(0xffff);
+                w.write_u16(ordinal);
+            }
+            None => w.write_utf16(self.class.name())
+        }
+        w.write_utf16(&self.text);
+        w.write_u16(0x0); // no creation data
+    }
+}
+
+impl BinaryWritable for [Accelerator] {
+    fn write_to<W: BinaryWriter>(&self, w: &mut W) {
+        // https://learn.microsoft.com/en-us/windows/win32/menurc/acceltableentry
+        for (i, accel) in self.iter().enumerate() {
+            let last = if i + 1 == self.len() { AccelFlags::LAST } else { 0x0 };
+            w.write_u16(accel.flags.bits() | last); // fFlags
+            w.write_u16(accel.key); // wAnsi
+            w.write_u16(accel.cmd); // wId
+            w.write_u16(0x0); // padding
+        }
     }
 }
 
@@ -89,8 +170,14 @@ impl BinaryWritable for Version {
     }
 }
 
-impl BinaryWritable for VersionInfo {
+/// A version info together with the language of its resource, which the string table refers to,
+/// and the language and code page of every version info in the file, which the translation lists.
+pub struct VersionResource<'a>(pub LanguageId, pub &'a VersionInfo, pub &'a [(LanguageId, u16)]);
+
+impl BinaryWritable for VersionResource<'_> {
     fn write_to<W: BinaryWriter>(&self, writer: &mut W) {
+        let VersionResource(language, version, translations) = *self;
+        let codepage = version.effective_codepage();
         let mut w = version::VersionWriter::new(writer);
         // https://learn.microsoft.com/en-us/windows/win32/menurc/vs-versioninfo
         w.write_field(
@@ -101,24 +188,29 @@ impl BinaryWritable for VersionInfo {
                 w.write_u32(0xFEEF04BD); //magic number
                 w.write_u32(1 << 16); // struct version
 
-                self.file_version.write_to(w);
-                self.product_version.write_to(w);
+                version.file_version.write_to(w);
+                version.product_version.write_to(w);
 
-                w.write_u32(0x3f); // fileflagsmask
-                w.write_u32(self.flags.iter().fold(0, |acc, f| acc | *f as u32));
-                w.write_u32(0x00040004); // VOS_NT_WINDOWS32
-                w.write_u32(self.file_type as u32); // VFT_APP
-                w.write_u32(0x0);
+                w.write_u32(version.file_flags_mask()); // fileflagsmask
+                w.write_u32(version.flags.iter().fold(0, |acc, f| acc | *f as u32));
+                w.write_u32(version.file_os as u32);
+                w.write_u32(version.file_type as u32);
+                w.write_u32(version.file_subtype);
 
-                w.write_u32(0x0); //Timestamp
-                w.write_u32(0x0);
+                w.write_u32((version.file_date >> 32) as u32); // dwFileDateMS
+                w.write_u32(version.file_date as u32); // dwFileDateLS
             }),
             |w| {
+                // an empty string table is not valid, so leave out both the strings and the translation
+                if version.strings.is_empty() {
+                    return;
+                }
                 // https://learn.microsoft.com/en-us/windows/win32/menurc/stringfileinfo
                 w.write_field(FieldType::Text, "StringFileInfo", FieldValue::none(), |w| {
                     // https://learn.microsoft.com/en-us/windows/win32/menurc/stringtable
-                    w.write_field(FieldType::Text, "000004b0", FieldValue::none(), |w| {
-                        for (k, v) in &self.strings {
+                    w.write_field(FieldType::Text, &format!("{:04x}{codepage:04x}", language.0), FieldValue::none(), |w| {
+                        for (k, v) in &version.strings {
+                            // the value length of text fields is in UTF-16 code units, including the terminator
                             let l = u16::try_from(v.encode_utf16().count() + 1).expect("Key too long");
                             // https://learn.microsoft.com/en-us/windows/win32/menurc/string-str
                             w.write_field(FieldType::Text, k, FieldValue::other(l), |w| w.write_utf16(v));
@@ -131,7 +223,12 @@ impl BinaryWritable for VersionInfo {
                         FieldType::Binary,
                         "Translation",
                         FieldValue::header(|w| {
-                            w.write_u32(0x04b00000);
+                            // the language of this resource comes first, so readers that only look at one pair pick its strings
+                            let others = translations.iter().filter(|(l, _)| *l != language);
+                            for (language, codepage) in std::iter::once(&(language, codepage)).chain(others) {
+                                w.write_u16(language.0);
+                                w.write_u16(*codepage);
+                            }
                         }),
                         |_| {}
                     )
@@ -142,6 +239,41 @@ impl BinaryWritable for VersionInfo {
     }
 }
 
+impl VersionInfo {
+    /// The code page of the string table.
+    /// The strings are always stored as UTF-16, the code page only tells how they were meant to be interpreted.
+    pub(crate) fn effective_codepage(&self) -> u16 {
+        const CODEPAGE_UNICODE: u16 = 0x04b0;
+        self.codepage.unwrap_or(CODEPAGE_UNICODE)
+    }
+
+    /// The flags mask to write, which covers all flags defined by `FileFlag` unless overridden.
+    pub(crate) fn file_flags_mask(&self) -> u32 {
+        self.flags_mask.unwrap_or(0x3f)
+    }
+
+    /// Returns the key of the first string that pushes the version info past the 16-bit length of its root field,
+    /// given the number of languages its translation lists.
+    pub(crate) fn find_overlong_string(&self, translations: usize) -> Option<&str> {
+        fn field_length(key: &str, value_length: usize) -> usize {
+            (6 + 2 * (key.encode_utf16().count() + 1)).next_multiple_of(4) + value_length
+        }
+        // the root field contains all others, so it is the first one to overflow
+        let mut length = field_length("VS_VERSION_INFO", 52).next_multiple_of(4)
+            + field_length("StringFileInfo", 0)
+            + field_length("040904b0", 0)
+            + field_length("VarFileInfo", 0)
+            + field_length("Translation", 4 * translations.max(1));
+        for (k, v) in &self.strings {
+            length += field_length(k, 2 * (v.encode_utf16().count() + 1)).next_multiple_of(4);
+            if length > u16::MAX as usize {
+                return Some(k);
+            }
+        }
+        None
+    }
+}
+
 mod version {
     use crate::binary::BinaryWriter;
 
@@ -181,6 +313,8 @@ mod version {
 
     impl<'a> VersionWriter<'a> {
         pub fn new(inner: &'a mut dyn BinaryWriter) -> Self {
+            // all alignment is relative to the start, which only works out if the start itself is aligned
+            debug_assert_eq!(inner.pos() % 4, 0, "version info must start on a 4-byte boundary");
             Self { start: inner.pos(), inner }
         }
 
@@ -194,13 +328,6 @@ mod version {
             self.write_bytes_at(location, &v.to_le_bytes())
         }
 
-        pub fn write_utf16(&mut self, text: &str) {
-            for c in text.encode_utf16() {
-                self.write_u16(c);
-            }
-            self.write_u16(0x0);
-        }
-
         pub fn write_field<F: FnOnce(&mut Self), B: FnOnce(&mut Self)>(&mut self, field_type: FieldType, key: &str, value: FieldValue<F>, body: B) {
             self.align_to(4);
             let field_start = self.pos();
@@ -209,6 +336,7 @@ mod version {
             self.write_u16(field_type as u16);
             self.write_utf16(key);
             self.align_to(4);
+            debug_assert_eq!(self.inner.pos() % 4, 0, "the value of {key:?} must start on a 4-byte boundary");
 
             match value {
                 FieldValue::None => {}
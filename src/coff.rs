@@ -1,25 +1,108 @@
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use std::io::Write;
-use std::time::SystemTime;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::str::FromStr;
 
 use crate::binary::{BinaryWritable, BinaryWriter};
-use crate::ResourceType;
+use crate::{EmbedError, LanguageId, ResourceType};
 
+/// The machine type of the COFF object.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum TargetType {
     Aarch64,
+    /// ARM64 code that can interoperate with emulated x64 code.
+    Arm64Ec,
     I386,
     X86_64
 }
 
 impl TargetType {
+    const ALL: [TargetType; 4] = [TargetType::Aarch64, TargetType::Arm64Ec, TargetType::I386, TargetType::X86_64];
+
     pub(crate) fn id(self) -> u16 {
         match self {
             TargetType::Aarch64 => 0xaa64,
+            TargetType::Arm64Ec => 0xa641,
             TargetType::I386 => 0x014c,
             TargetType::X86_64 => 0x8664
         }
     }
+
+    /// Gets the target type of the `Machine` field of a COFF header.
+    pub fn from_machine(machine: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|target| target.id() == machine)
+    }
+
+    /// Maps a target architecture as found in `CARGO_CFG_TARGET_ARCH` or at the start of a target triple.
+    pub(crate) fn from_arch(arch: &str) -> Option<Self> {
+        match arch {
+            "x86_64" => Some(TargetType::X86_64),
+            "x86" | "i386" | "i586" | "i686" => Some(TargetType::I386),
+            "aarch64" => Some(TargetType::Aarch64),
+            "arm64ec" => Some(TargetType::Arm64Ec),
+            _ => None
+        }
+    }
+
+    /// Gets the target type of a Windows target triple like `x86_64-pc-windows-msvc` or `i686-pc-windows-gnu`.
+    pub fn from_triple(triple: &str) -> Result<Self, ParseTargetError> {
+        let mut components = triple.split('-');
+        let arch = components.next().unwrap_or_default();
+        if !components.any(|os| os == "windows") {
+            return Err(ParseTargetError::NotWindows(triple.to_string()));
+        }
+        Self::from_arch(arch).ok_or_else(|| ParseTargetError::UnsupportedArch(arch.to_string()))
+    }
+}
+
+impl FromStr for TargetType {
+    type Err = ParseTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_triple(s)
+    }
+}
+
+/// The error returned when parsing a [`TargetType`] from a target triple fails.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseTargetError {
+    /// The triple does not describe a Windows target.
+    NotWindows(String),
+    /// The architecture of the triple is not supported.
+    UnsupportedArch(String)
+}
+
+impl Display for ParseTargetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseTargetError::NotWindows(triple) => write!(f, "{triple:?} is not a windows target"),
+            ParseTargetError::UnsupportedArch(arch) => write!(f, "unsupported target arch {arch:?}")
+        }
+    }
+}
+
+impl Error for ParseTargetError {}
+
+/// The toolchain used for linking.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TargetEnv {
+    /// The MSVC linker (`link.exe` or `lld-link`).
+    Msvc,
+    /// The GNU linker (`ld`) as used by the `*-pc-windows-gnu` targets.
+    Gnu
+}
+
+impl TargetEnv {
+    /// The file extension of the object file expected by the linker.
+    pub(crate) fn object_extension(self) -> &'static str {
+        match self {
+            TargetEnv::Msvc => "lib",
+            TargetEnv::Gnu => "o"
+        }
+    }
 }
 
 struct Section {
@@ -30,6 +113,34 @@ struct Section {
     number_of_relocations: usize
 }
 
+impl Section {
+    /// The checksum of the raw data for the section definition symbol, computed the same way as by `link.exe` and LLVM.
+    fn checksum(&self, file: &mut FileWriter) -> u32 {
+        jam_crc(file.slice(self.pointer_to_raw_data, self.size_of_raw_data))
+    }
+}
+
+/// CRC-32 with an initial value of 0 and without the final inversion.
+fn jam_crc(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    data.iter()
+        .fold(0, |crc, &byte| TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
 #[derive(Default, Copy, Clone)]
 enum Symbol {
     #[default]
@@ -40,10 +151,10 @@ enum Symbol {
     },
     SectionAux {
         length: u32,
-        number_of_relocations: u16
+        number_of_relocations: u16,
+        checksum: u32
     },
     Resource {
-        name: [u8; 8],
         offset: u32,
         section_number: u16
     }
@@ -53,49 +164,181 @@ pub struct CoffWriter {
     target_type: TargetType,
     table: BTreeMap<ResourceType, BTreeMap<ResourceId, BTreeMap<LanguageId, ResourceLocation>>>,
     data: FileWriter,
-    symbols: Vec<Symbol>
+    symbols: Vec<Symbol>,
+    blobs: BTreeMap<u64, Vec<ResourceLocation>>,
+    codepage: u32,
+    resource_version: (u16, u16),
+    single_section: bool
 }
 
 impl CoffWriter {
     const TABLE_SYMBOL: usize = 0;
     const DATA_SYMBOL: usize = 2;
 
-    pub fn new(target_type: TargetType) -> Self {
+    /// Creates a writer with room for `capacity` bytes of resource data.
+    pub fn with_capacity(target_type: TargetType, capacity: usize) -> Self {
         Self {
             target_type,
             table: Default::default(),
-            data: Default::default(),
-            symbols: vec![Symbol::default(); 4]
+            data: FileWriter::with_capacity(capacity),
+            symbols: vec![Symbol::default(); 4],
+            blobs: Default::default(),
+            codepage: 0,
+            resource_version: (0, 0),
+            single_section: false
         }
     }
 
+    /// Puts the directory and the data into a single `.rsrc` section instead of `.rsrc$01` and `.rsrc$02`.
+    /// GNU `ld` only merges the resources of multiple objects in this layout.
+    pub fn with_single_section(mut self) -> Self {
+        assert!(self.table.is_empty(), "The layout must be chosen before adding resources");
+        self.single_section = true;
+        self.symbols.truncate(Self::DATA_SYMBOL);
+        self
+    }
+
+    pub fn set_codepage(&mut self, codepage: u32) {
+        self.codepage = codepage;
+    }
+
+    /// Sets the version fields of the top-level resource directory.
+    pub fn set_resource_version(&mut self, major: u16, minor: u16) {
+        self.resource_version = (major, minor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// The type, id, language and data size of every resource in the order of the directory.
+    pub fn entries(&self) -> impl Iterator<Item = (ResourceType, &ResourceId, LanguageId, usize)> + '_ {
+        self.table.iter().flat_map(|(ty, ids)| {
+            ids.iter().flat_map(move |(id, languages)| {
+                languages
+                    .iter()
+                    .map(move |(language, location)| (*ty, id, *language, location.size))
+            })
+        })
+    }
+
+    /// The data of every resource in the order of [`CoffWriter::entries`].
+    pub fn entry_data(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.table
+            .values()
+            .flat_map(|ids| ids.values())
+            .flat_map(|languages| languages.values())
+            .map(|location| {
+                // padding at the very end is only reserved, it turns into zeros once the object is written
+                let written = &self.data.data[location.offset.min(self.data.data.len())..];
+                let mut data = written[..location.size.min(written.len())].to_vec();
+                data.resize(location.size, 0);
+                data
+            })
+    }
+
     pub fn add_resource<W: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, id: u32, data: &W) {
+        self.add_resource_with_language(ty, id, LanguageId::LANG_US, data)
+    }
+
+    pub fn add_resource_with_language<W: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, id: u32, language: LanguageId, data: &W) {
+        self.insert(ty, ResourceId::Id(id), language, data)
+    }
+
+    /// Adds a resource that is identified by a string instead of an id. The name has to be uppercase already.
+    pub fn add_named_resource<W: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, name: &str, language: LanguageId, data: &W) {
+        self.insert(ty, ResourceId::Name(name.to_string()), language, data)
+    }
+
+    fn insert<W: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, id: ResourceId, language: LanguageId, data: &W) {
         let (offset, size) = {
             let offset = self.data.pos();
+            debug_assert_eq!(offset % 8, 0, "resource data must start on an 8-byte boundary");
             data.write_to(&mut self.data);
             (offset, self.data.pos() - offset)
         };
-        self.data.align_to(8);
-        let mut name = [0u8; 8];
-        write!(name.as_mut_slice(), "$R{:06X}", offset).expect("Failed to generate symbol name");
-        let symbol_id = self.symbols.len();
-        self.symbols.push(Symbol::Resource {
-            name,
-            offset: offset as u32,
-            section_number: 2
+
+        // Identical resources share their data and only get separate directory entries
+        let hash = {
+            let mut hasher = DefaultHasher::new();
+            self.data.slice(offset, size).hash(&mut hasher);
+            hasher.finish()
+        };
+        let duplicate = self.blobs.get(&hash).and_then(|candidates| {
+            candidates
+                .iter()
+                .find(|c| c.size == size && self.data.data[c.offset..c.offset + c.size] == self.data.data[offset..offset + size])
+                .copied()
         });
+        let location = match duplicate {
+            Some(location) => {
+                self.data.truncate(offset);
+                location
+            }
+            None => {
+                self.data.align_to(8);
+                let symbol_id = self.symbols.len();
+                self.symbols.push(Symbol::Resource {
+                    offset: offset as u32,
+                    section_number: 2
+                });
+                let location = ResourceLocation { offset, size, symbol_id };
+                self.blobs.entry(hash).or_default().push(location);
+                location
+            }
+        };
 
-        self.table
+        let description = format!("type {}, id {id}, language {:#06x}", u32::from(ty), u32::from(language));
+        let previous = self
+            .table
             .entry(ty)
             .or_default()
-            .entry(ResourceId(id))
+            .entry(id)
             .or_default()
-            .insert(LanguageId::LANG_US, ResourceLocation { offset, size, symbol_id });
+            .insert(language, location);
+        assert!(previous.is_none(), "Duplicate resource: {description}");
+    }
+
+    /// Returns the size of the resource directory including the data entries, and the number of data entries.
+    fn directory_size(&self) -> (usize, usize) {
+        const DIRECTORY_HEADER_SIZE: usize = 16;
+        const DATA_ENTRY_SIZE: usize = 16;
+        let directory = |entries: usize| DIRECTORY_HEADER_SIZE + entries * RESOURCE_TABLE_ENTRY_SIZE;
+        let mut size = directory(self.table.len());
+        let mut number_of_entries = 0;
+        for name in self.table.keys().filter_map(|ty| ty.name()) {
+            size += 2 + name.encode_utf16().count() * 2;
+        }
+        for ids in self.table.values() {
+            size += directory(ids.len());
+            for name in ids.keys().filter_map(|id| id.name()) {
+                size += 2 + name.encode_utf16().count() * 2;
+            }
+            for languages in ids.values() {
+                size += directory(languages.len()) + languages.len() * DATA_ENTRY_SIZE;
+                number_of_entries += languages.len();
+            }
+        }
+        (size, number_of_entries)
+    }
+
+    /// The size of the names that don't fit into their symbols, once the data has been placed at `data_offset` of its section.
+    fn string_table_size(&self, data_offset: usize) -> usize {
+        self.symbols
+            .iter()
+            .filter_map(|symbol| match *symbol {
+                Symbol::Resource { offset, .. } => Some(resource_symbol_name_len(offset + data_offset as u32)),
+                _ => None
+            })
+            .filter(|&len| len > 8)
+            .map(|len| len + 1)
+            .sum()
     }
 
     fn write_symbol_table(&mut self, file: &mut FileWriter) -> (usize, usize) {
         file.align_to(4);
         let symbol_table_pointer = file.pos();
+        let mut string_table = StringTable::default();
 
         for symbol in &self.symbols {
             match *symbol {
@@ -110,19 +353,17 @@ impl CoffWriter {
                 }
                 Symbol::SectionAux {
                     length,
-                    number_of_relocations
+                    number_of_relocations,
+                    checksum
                 } => {
                     file.write_u32(length); // Length
                     file.write_u16(number_of_relocations); // Number of relocations
                     file.write_u16(0); // Number of lines
-                    file.reserve(10); // Checksum, Number, Selection, Unused
+                    file.write_u32(checksum); // Checksum
+                    file.reserve(6); // Number, Selection, Unused
                 }
-                Symbol::Resource {
-                    name,
-                    section_number,
-                    offset
-                } => {
-                    file.write_bytes(&name); // Name
+                Symbol::Resource { section_number, offset } => {
+                    string_table.write_name(file, &resource_symbol_name(offset)); // Name
                     file.write_u32(offset); // Value
                     file.write_u16(section_number); // Section number
                     file.write_u16(0); // Type
@@ -132,48 +373,79 @@ impl CoffWriter {
             }
         }
         let number_of_symbols = self.symbols.len();
+        string_table.write_to(file);
 
         (symbol_table_pointer, number_of_symbols)
     }
 
-    fn write_table_section(&mut self, file: &mut FileWriter) -> Section {
-        file.mark_section_start();
-        let pointer_to_raw_data = file.pos();
-
+    /// Writes the resource directory and returns the offsets of the data entries that need a relocation, with their symbols.
+    fn write_directory(&self, file: &mut FileWriter) -> Vec<(usize, usize)> {
         let mut relocations = Vec::new();
+        let codepage = self.codepage;
 
-        file.write_table(&self.table, |file, entry| {
-            file.write_table(entry, |file, entry| {
+        let root = file.pos();
+        let mut id_names = Vec::new();
+        let type_names = file.write_table(&self.table, |file, entry| {
+            id_names.extend(file.write_table(entry, |file, entry| {
                 file.write_table(entry, |file, entry| {
                     relocations.push((file.current_offset(), entry.symbol_id));
                     file.write_u32(0); // Data RVA
                     file.write_u32(entry.size as u32); // Size
-                    file.write_u32(0); // Code page
+                    file.write_u32(codepage); // Code page
                     file.write_u32(entry.size as u32); // Reserved
                     false
                 });
                 true
-            });
+            }));
             true
         });
-        file.align_to(4);
-        let size_of_raw_data = file.pos() - pointer_to_raw_data;
-        let pointer_to_relocations = file.pos();
-        let number_of_relocations = relocations.len();
+        // only the top-level directory carries the version, the subdirectories are left at 0
+        let end = file.pos();
+        file.set_pos(root + 8);
+        file.write_u16(self.resource_version.0); // MajorVersion
+        file.write_u16(self.resource_version.1); // MinorVersion
+        file.set_pos(end);
+        // the names of named entries are stored as counted UTF-16 strings after the tables
+        for (entry, name) in type_names.into_iter().chain(id_names) {
+            let offset = file.current_offset();
+            file.write_u16(name.encode_utf16().count() as u16);
+            for c in name.encode_utf16() {
+                file.write_u16(c);
+            }
+            let end = file.pos();
+            file.set_pos(entry);
+            file.write_u32(offset as u32 | 1 << 31);
+            file.set_pos(end);
+        }
         relocations.sort_by_key(|&(_, symbol_id)| symbol_id);
-        for (rva, symbol_id) in relocations {
+        relocations
+    }
+
+    fn write_relocations(&self, file: &mut FileWriter, relocations: &[(usize, usize)]) -> usize {
+        let pointer_to_relocations = file.pos();
+        for &(rva, symbol_id) in relocations {
             file.write_u32(rva as u32);
             file.write_u32(symbol_id as u32);
             file.write_u16(RelocationType::Rva32.id(self.target_type));
         }
         file.align_to(4);
+        pointer_to_relocations
+    }
+
+    fn write_table_section(&mut self, file: &mut FileWriter) -> Section {
+        file.mark_section_start();
+        let pointer_to_raw_data = file.pos();
+        let relocations = self.write_directory(file);
+        file.align_to(4);
+        let size_of_raw_data = file.pos() - pointer_to_raw_data;
+        let pointer_to_relocations = self.write_relocations(file, &relocations);
 
         Section {
             name: *b".rsrc$01",
             pointer_to_raw_data,
             size_of_raw_data,
             pointer_to_relocations,
-            number_of_relocations
+            number_of_relocations: relocations.len()
         }
     }
 
@@ -192,8 +464,77 @@ impl CoffWriter {
         }
     }
 
-    fn write_sections(&mut self, file: &mut FileWriter) -> [Section; 2] {
+    /// Writes the directory and the data into a single `.rsrc` section, like `windres` does.
+    fn write_single_section(&mut self, file: &mut FileWriter) -> Result<Section, EmbedError> {
+        file.mark_section_start();
+        let pointer_to_raw_data = file.pos();
+        let relocations = self.write_directory(file);
+        file.align_to(8);
+        let data_offset = file.current_offset();
+        if data_offset > MAX_DIRECTORY_OFFSET {
+            return Err(EmbedError::ObjectTooLarge("resource directory"));
+        }
+        if relocations.len() > u16::MAX as usize {
+            return Err(EmbedError::ObjectTooLarge("relocation table"));
+        }
+        let file_size = (file.pos() + self.data.pos()).next_multiple_of(4)
+            + relocations.len() * RELOCATION_SIZE
+            + self.symbols.len() * SYMBOL_SIZE
+            + 4
+            + self.string_table_size(data_offset);
+        if file_size > u32::MAX as usize {
+            return Err(EmbedError::ObjectTooLarge("resource data"));
+        }
+        file.write_bytes(&self.data.data);
+        file.align_to(4);
+        let size_of_raw_data = file.pos() - pointer_to_raw_data;
+        let pointer_to_relocations = self.write_relocations(file, &relocations);
+
+        // the resource symbols were created relative to the data, which now follows the directory
+        for symbol in &mut self.symbols {
+            if let Symbol::Resource { offset, section_number } = symbol {
+                *offset += data_offset as u32;
+                *section_number = 1;
+            }
+        }
+        let section = Section {
+            name: *b".rsrc\0\0\0",
+            pointer_to_raw_data,
+            size_of_raw_data,
+            pointer_to_relocations,
+            number_of_relocations: relocations.len()
+        };
+        self.symbols[Self::TABLE_SYMBOL] = Symbol::Section {
+            name: section.name,
+            section_number: 1
+        };
+        self.symbols[Self::TABLE_SYMBOL + 1] = Symbol::SectionAux {
+            length: section.size_of_raw_data as u32,
+            number_of_relocations: section.number_of_relocations as u16,
+            checksum: section.checksum(file)
+        };
+        Ok(section)
+    }
+
+    fn write_sections(&mut self, file: &mut FileWriter) -> Result<Vec<Section>, EmbedError> {
+        if self.single_section {
+            return Ok(vec![self.write_single_section(file)?]);
+        }
         let table_section = self.write_table_section(file);
+
+        // Everything in the object is addressed with 32 bits, directory offsets even lose their top bit to the subdirectory flag.
+        // Check before copying the data, so an oversized object fails early instead of being truncated.
+        if table_section.size_of_raw_data > MAX_DIRECTORY_OFFSET {
+            return Err(EmbedError::ObjectTooLarge("resource directory"));
+        }
+        if table_section.number_of_relocations > u16::MAX as usize {
+            return Err(EmbedError::ObjectTooLarge("relocation table"));
+        }
+        let file_size = (file.pos() + self.data.pos()).next_multiple_of(4) + self.symbols.len() * SYMBOL_SIZE + 4 + self.string_table_size(0);
+        if file_size > u32::MAX as usize {
+            return Err(EmbedError::ObjectTooLarge("resource data"));
+        }
+
         let data_section = self.write_data_section(file);
 
         self.symbols[Self::TABLE_SYMBOL] = Symbol::Section {
@@ -202,7 +543,8 @@ impl CoffWriter {
         };
         self.symbols[Self::TABLE_SYMBOL + 1] = Symbol::SectionAux {
             length: table_section.size_of_raw_data as u32,
-            number_of_relocations: table_section.number_of_relocations as u16
+            number_of_relocations: table_section.number_of_relocations as u16,
+            checksum: table_section.checksum(file)
         };
         self.symbols[Self::DATA_SYMBOL] = Symbol::Section {
             name: data_section.name,
@@ -210,27 +552,49 @@ impl CoffWriter {
         };
         self.symbols[Self::DATA_SYMBOL + 1] = Symbol::SectionAux {
             length: data_section.size_of_raw_data as u32,
-            number_of_relocations: data_section.number_of_relocations as u16
+            number_of_relocations: data_section.number_of_relocations as u16,
+            checksum: data_section.checksum(file)
         };
-        [table_section, data_section]
+        Ok(vec![table_section, data_section])
     }
 
-    pub fn finish(mut self) -> Vec<u8> {
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map_or(0, |d| d.as_secs() as u32);
+    /// The exact size of the object [`CoffWriter::finish`] would produce, without writing it.
+    pub fn size(&self) -> usize {
+        let (directory_size, number_of_entries) = self.directory_size();
+        // the data is only padded up to the position, which is not written out after the last resource
+        let data_size = self.data.data.len();
+        let relocations_size = (number_of_entries * RELOCATION_SIZE).next_multiple_of(4);
+        // the padding is relative to the start of the file, not the start of the section
+        let (sections_end, data_offset) = match self.single_section {
+            true => {
+                let section_start = FILE_HEADER_SIZE + SECTION_HEADER_SIZE;
+                let data_offset = (section_start + directory_size).next_multiple_of(8) - section_start;
+                (
+                    (section_start + data_offset + data_size).next_multiple_of(4) + relocations_size,
+                    data_offset
+                )
+            }
+            false => {
+                let table_end = (FILE_HEADER_SIZE + 2 * SECTION_HEADER_SIZE + directory_size).next_multiple_of(4) + relocations_size;
+                ((table_end + data_size).next_multiple_of(4), 0)
+            }
+        };
+        sections_end + self.symbols.len() * SYMBOL_SIZE + 4 + self.string_table_size(data_offset)
+    }
 
-        let mut file = FileWriter::default();
+    pub fn finish(mut self) -> Result<Vec<u8>, EmbedError> {
+        let number_of_sections = if self.single_section { 1 } else { 2 };
+        // The sections are written out of order, so allocate the whole file up front instead of growing it on every jump
+        let mut file = FileWriter::with_capacity(self.size());
 
-        file.set_pos(FILE_HEADER_SIZE + SECTION_HEADER_SIZE * 2);
-        let sections = self.write_sections(&mut file);
+        file.set_pos(FILE_HEADER_SIZE + SECTION_HEADER_SIZE * number_of_sections);
+        let sections = self.write_sections(&mut file)?;
         let (symbol_table_pointer, symbol_numer) = self.write_symbol_table(&mut file);
-        file.write_bytes(&[0; 4]); // String table
 
         file.set_pos(0);
         file.write_u16(self.target_type.id());
-        file.write_u16(2); // number of sections
-        file.write_u32(timestamp);
+        file.write_u16(number_of_sections as u16);
+        file.write_u32(0); // timestamp, left empty to keep the output reproducible
         file.write_u32(symbol_table_pointer as u32);
         file.write_u32(symbol_numer as u32);
         file.write_u16(0); // optional header size
@@ -247,62 +611,168 @@ impl CoffWriter {
             file.write_u32(0); // pointer to line numbers
             file.write_u16(section.number_of_relocations as u16);
             file.write_u16(0); // number of line numbers
-            file.write_u32(IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ | IMAGE_SCN_MEM_WRITE);
+            file.write_u32(IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ);
+            // resources are read-only
+        }
+        assert_eq!(file.pos(), FILE_HEADER_SIZE + number_of_sections * SECTION_HEADER_SIZE);
+
+        Ok(file.data)
+    }
+}
+
+/// Resource symbols are named after their offset, like the ones generated by `cvtres.exe`.
+/// Offsets past `0xFFFFFF` produce names longer than eight bytes, which have to go into the string table.
+fn resource_symbol_name(offset: u32) -> String {
+    format!("$R{offset:06X}")
+}
+
+/// The length of [`resource_symbol_name`], without formatting it.
+fn resource_symbol_name_len(offset: u32) -> usize {
+    let digits = (u32::BITS - offset.leading_zeros()).div_ceil(4).max(6);
+    2 + digits as usize
+}
+
+/// The COFF string table that holds all symbol names longer than eight bytes.
+#[derive(Default)]
+struct StringTable {
+    data: Vec<u8>
+}
+
+impl StringTable {
+    /// Writes the name field of a symbol, moving the name into the string table if it doesn't fit.
+    fn write_name(&mut self, file: &mut FileWriter, name: &str) {
+        if name.len() <= 8 {
+            let mut short = [0u8; 8];
+            short[..name.len()].copy_from_slice(name.as_bytes());
+            file.write_bytes(&short);
+        } else {
+            // the offset includes the size field at the start of the table
+            let offset = 4 + self.data.len();
+            file.write_u32(0);
+            file.write_u32(offset as u32);
+            self.data.extend_from_slice(name.as_bytes());
+            self.data.push(0);
+        }
+    }
+
+    fn write_to(&self, file: &mut FileWriter) {
+        file.write_u32((4 + self.data.len()) as u32); // Size
+        file.write_bytes(&self.data);
+    }
+}
+
+/// A key of a resource directory table.
+pub(crate) trait DirectoryKey {
+    /// The id of the entry, which is ignored for named entries.
+    fn id(&self) -> u32;
+
+    /// The name of the entry, if it is identified by a string instead of an id. Named entries must be sorted first.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl DirectoryKey for ResourceType {
+    fn id(&self) -> u32 {
+        u32::from(*self)
+    }
+
+    fn name(&self) -> Option<&str> {
+        ResourceType::name(*self)
+    }
+}
+
+impl DirectoryKey for ResourceId {
+    fn id(&self) -> u32 {
+        match self {
+            ResourceId::Id(id) => *id,
+            ResourceId::Name(_) => 0
         }
-        assert_eq!(file.pos(), FILE_HEADER_SIZE + 2 * SECTION_HEADER_SIZE);
+    }
 
-        file.data
+    fn name(&self) -> Option<&str> {
+        match self {
+            ResourceId::Name(name) => Some(name),
+            ResourceId::Id(_) => None
+        }
+    }
+}
+
+impl DirectoryKey for LanguageId {
+    fn id(&self) -> u32 {
+        u32::from(*self)
     }
 }
 
 impl FileWriter {
-    pub fn write_table<K, V, F>(&mut self, table: &BTreeMap<K, V>, mut write_entry: F)
+    /// Writes a directory table and the subtrees of its entries.
+    /// Returns the positions of the named entries, which still need the offset of their name.
+    pub fn write_table<'a, K, V, F>(&mut self, table: &'a BTreeMap<K, V>, mut write_entry: F) -> Vec<(usize, &'a str)>
     where
-        K: Copy + Into<u32>,
-        F: FnMut(&mut Self, &V) -> bool
+        K: DirectoryKey,
+        F: FnMut(&mut Self, &'a V) -> bool
     {
+        let named_entries = table.keys().filter(|key| key.name().is_some()).count();
         self.write_u32(0); // Characteristics
         self.write_u32(0); // TimeDateStamp
         self.write_u16(0); // MajorVersion
         self.write_u16(0); // MinorVersion
-        self.write_u16(0); // NumberOfNamedEntries
-        self.write_u16(table.len() as u16); // NumberOfIdEntries
+        self.write_u16(named_entries as u16); // NumberOfNamedEntries
+        self.write_u16((table.len() - named_entries) as u16); // NumberOfIdEntries
         let table_base = self.pos();
         let mut frontier = table_base + table.len() * RESOURCE_TABLE_ENTRY_SIZE;
-        for (i, (ty, entry)) in table.iter().enumerate() {
+        let mut names = Vec::new();
+        for (i, (key, entry)) in table.iter().enumerate() {
             self.set_pos(frontier);
             let offset = self.current_offset();
             let subdir = write_entry(self, entry);
             frontier = self.pos();
             self.set_pos(table_base + i * RESOURCE_TABLE_ENTRY_SIZE);
-            self.write_u32((*ty).into());
+            match key.name() {
+                Some(name) => {
+                    names.push((self.pos(), name));
+                    self.write_u32(0);
+                }
+                None => self.write_u32(key.id())
+            }
             self.write_u32(offset as u32 | (subdir as u32) << 31);
         }
         self.set_pos(frontier);
+        names
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
-#[repr(transparent)]
-struct ResourceId(u32);
+/// The name of a resource, which is either an id or an uppercase string.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum ResourceId {
+    Name(String),
+    Id(u32)
+}
 
-impl From<ResourceId> for u32 {
-    fn from(id: ResourceId) -> u32 {
-        id.0
+// Named entries come first and are sorted by their UTF-16 code units, which is how Windows searches them
+impl Ord for ResourceId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (ResourceId::Name(a), ResourceId::Name(b)) => a.encode_utf16().cmp(b.encode_utf16()),
+            (ResourceId::Name(_), ResourceId::Id(_)) => Ordering::Less,
+            (ResourceId::Id(_), ResourceId::Name(_)) => Ordering::Greater,
+            (ResourceId::Id(a), ResourceId::Id(b)) => a.cmp(b)
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
-#[repr(transparent)]
-struct LanguageId(u32);
-
-impl LanguageId {
-    const LANG_US: Self = Self(0x0409);
+impl PartialOrd for ResourceId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-impl From<LanguageId> for u32 {
-    fn from(id: LanguageId) -> u32 {
-        id.0
+impl Display for ResourceId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceId::Name(name) => write!(f, "{name:?}"),
+            ResourceId::Id(id) => write!(f, "{id}")
+        }
     }
 }
 
@@ -313,24 +783,41 @@ struct ResourceLocation {
     symbol_id: usize
 }
 
+/// The kinds of relocations in a COFF object, independent of the architecture.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum RelocationType {
-    Rva32
+pub enum RelocationType {
+    /// The 32-bit address of the target relative to the image base (`ADDR32NB`/`DIR32NB`).
+    /// This is what the resource directory uses to point at the resource data.
+    Rva32,
+    /// The 32-bit absolute address of the target (`ADDR32`/`DIR32`).
+    Addr32
 }
 
 impl RelocationType {
+    const ALL: [RelocationType; 2] = [RelocationType::Rva32, RelocationType::Addr32];
+
+    /// The architecture specific id of the relocation, as stored in the object file.
     pub fn id(self, target: TargetType) -> u16 {
+        const IMAGE_REL_AMD64_ADDR32: u16 = 0x0002;
         const IMAGE_REL_AMD64_ADDR32NB: u16 = 0x0003;
+        const IMAGE_REL_ARM64_ADDR32: u16 = 0x0001;
         const IMAGE_REL_ARM64_ADDR32NB: u16 = 0x0002;
+        const IMAGE_REL_I386_DIR32: u16 = 0x0006;
         const IMAGE_REL_I386_DIR32NB: u16 = 0x0007;
-        match self {
-            RelocationType::Rva32 => match target {
-                TargetType::Aarch64 => IMAGE_REL_ARM64_ADDR32NB,
-                TargetType::I386 => IMAGE_REL_I386_DIR32NB,
-                TargetType::X86_64 => IMAGE_REL_AMD64_ADDR32NB
-            }
+        match (self, target) {
+            (RelocationType::Rva32, TargetType::Aarch64 | TargetType::Arm64Ec) => IMAGE_REL_ARM64_ADDR32NB,
+            (RelocationType::Rva32, TargetType::I386) => IMAGE_REL_I386_DIR32NB,
+            (RelocationType::Rva32, TargetType::X86_64) => IMAGE_REL_AMD64_ADDR32NB,
+            (RelocationType::Addr32, TargetType::Aarch64 | TargetType::Arm64Ec) => IMAGE_REL_ARM64_ADDR32,
+            (RelocationType::Addr32, TargetType::I386) => IMAGE_REL_I386_DIR32,
+            (RelocationType::Addr32, TargetType::X86_64) => IMAGE_REL_AMD64_ADDR32
         }
     }
+
+    /// The inverse of [`RelocationType::id`]. Returns `None` for relocations that are not described by this type.
+    pub fn from_id(target: TargetType, id: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|ty| ty.id(target) == id)
+    }
 }
 
 #[derive(Default)]
@@ -341,6 +828,13 @@ pub struct FileWriter {
 }
 
 impl FileWriter {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
     pub fn set_pos(&mut self, pos: usize) {
         self.current_position = pos;
     }
@@ -352,6 +846,20 @@ impl FileWriter {
     pub fn current_offset(&self) -> usize {
         self.current_position - self.section_start
     }
+
+    /// Returns the bytes in the given range, padding the file with zeros if needed.
+    pub fn slice(&mut self, start: usize, len: usize) -> &[u8] {
+        if self.data.len() < start + len {
+            self.data.resize(start + len, 0);
+        }
+        &self.data[start..start + len]
+    }
+
+    /// Discards everything after `pos`.
+    pub fn truncate(&mut self, pos: usize) {
+        self.data.truncate(pos);
+        self.current_position = pos;
+    }
 }
 
 impl BinaryWriter for FileWriter {
@@ -379,7 +887,11 @@ impl BinaryWriter for FileWriter {
 const FILE_HEADER_SIZE: usize = 20;
 const SECTION_HEADER_SIZE: usize = 40;
 
+const SYMBOL_SIZE: usize = 18;
+const RELOCATION_SIZE: usize = 10;
+
 const RESOURCE_TABLE_ENTRY_SIZE: usize = 8;
+const MAX_DIRECTORY_OFFSET: usize = 0x7FFFFFFF;
 
 const IMAGE_SYM_CLASS_STATIC: u8 = 0x03;
 
@@ -387,4 +899,75 @@ const IMAGE_FILE_32BIT_MACHINE: u16 = 0x0100;
 
 const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x00000040;
 const IMAGE_SCN_MEM_READ: u32 = 0x40000000;
-const IMAGE_SCN_MEM_WRITE: u32 = 0x80000000;
+
+/// Wraps a single object in a COFF archive, consisting of the two linker members without any symbols and the object itself.
+///
+/// The resources don't define external symbols, so linkers never pull the object out of the archive on their own.
+pub(crate) fn write_archive(member_name: &str, object: &[u8]) -> Vec<u8> {
+    const SIGNATURE: &[u8] = b"!<arch>\n";
+    const MEMBER_HEADER_SIZE: usize = 60;
+
+    fn write_member(archive: &mut Vec<u8>, name: &str, data: &[u8]) {
+        let header = format!("{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", 0, "", "", 0, data.len());
+        debug_assert_eq!(header.len(), MEMBER_HEADER_SIZE);
+        archive.extend_from_slice(header.as_bytes());
+        archive.extend_from_slice(data);
+        // members start on an even offset
+        if !archive.len().is_multiple_of(2) {
+            archive.push(b'\n');
+        }
+    }
+
+    // first linker member: big-endian symbol count and offsets
+    let first_linker_member = 0u32.to_be_bytes();
+    // second linker member: little-endian member count and offsets, followed by the (empty) symbol index
+    let object_offset = SIGNATURE.len() + 2 * MEMBER_HEADER_SIZE + first_linker_member.len() + 12;
+    let mut second_linker_member = Vec::new();
+    second_linker_member.extend_from_slice(&1u32.to_le_bytes());
+    second_linker_member.extend_from_slice(&(object_offset as u32).to_le_bytes());
+    second_linker_member.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut archive = SIGNATURE.to_vec();
+    write_member(&mut archive, "/", &first_linker_member);
+    write_member(&mut archive, "/", &second_linker_member);
+    assert_eq!(archive.len(), object_offset);
+    write_member(&mut archive, &format!("{member_name}/"), object);
+    archive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_symbol_names_stay_unique() {
+        let offsets = [0, 8, 0xFF_FFF8, 0x100_0000, 0xFFF_FFF8, 0x1000_0000, u32::MAX - 7];
+        let names: Vec<_> = offsets
+            .iter()
+            .map(|&offset| resource_symbol_name(offset))
+            .collect();
+        assert_eq!(names[2..4], ["$RFFFFF8", "$R1000000"]);
+        assert_eq!(names[6], "$RFFFFFFF8");
+        for (name, &offset) in names.iter().zip(&offsets) {
+            assert_eq!(name.len(), resource_symbol_name_len(offset));
+        }
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+    }
+
+    #[test]
+    fn oversized_data_is_rejected() {
+        for single_section in [false, true] {
+            let mut writer = CoffWriter::with_capacity(TargetType::X86_64, 0);
+            if single_section {
+                writer = writer.with_single_section();
+            }
+            writer.add_resource(ResourceType::Html, 1, &vec![1u8; 16]);
+            // reserving only moves the position, so the data section claims to be 4 GiB larger without allocating it
+            writer.data.reserve(u32::MAX as usize);
+            assert!(matches!(writer.finish(), Err(EmbedError::ObjectTooLarge("resource data"))));
+        }
+    }
+}
@@ -0,0 +1,86 @@
+/// A dialog box template in the classic (non-extended) `DLGTEMPLATE` format.
+///
+/// The style flags are passed through as-is, with the exception of `DS_SETFONT` which is set automatically if a font is specified.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DialogTemplate {
+    pub style: u32,
+    pub extended_style: u32,
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+    pub title: String,
+    pub font: Option<DialogFont>,
+    pub controls: Vec<DialogControl>
+}
+
+impl DialogTemplate {
+    /// `DS_SETFONT`: The template contains a font specification.
+    pub(crate) const DS_SETFONT: u32 = 0x40;
+}
+
+/// The font used for the dialog and all of its controls.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DialogFont {
+    pub point_size: u16,
+    pub name: String
+}
+
+/// A single control of a dialog box.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DialogControl {
+    pub class: ControlClass,
+    pub id: u16,
+    pub text: String,
+    pub style: u32,
+    pub extended_style: u32,
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16
+}
+
+/// The window class of a dialog control.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ControlClass {
+    Button,
+    Edit,
+    Static,
+    ListBox,
+    ScrollBar,
+    ComboBox,
+    /// A window class registered by the application.
+    Custom(String)
+}
+
+impl ControlClass {
+    /// The predefined ordinal of the class, if there is one.
+    pub(crate) fn ordinal(&self) -> Option<u16> {
+        match self {
+            ControlClass::Button => Some(0x0080),
+            ControlClass::Edit => Some(0x0081),
+            ControlClass::Static => Some(0x0082),
+            ControlClass::ListBox => Some(0x0083),
+            ControlClass::ScrollBar => Some(0x0084),
+            ControlClass::ComboBox => Some(0x0085),
+            ControlClass::Custom(_) => None
+        }
+    }
+
+    /// The name of the window class.
+    pub fn name(&self) -> &str {
+        match self {
+            ControlClass::Button => "BUTTON",
+            ControlClass::Edit => "EDIT",
+            ControlClass::Static => "STATIC",
+            ControlClass::ListBox => "LISTBOX",
+            ControlClass::ScrollBar => "SCROLLBAR",
+            ControlClass::ComboBox => "COMBOBOX",
+            ControlClass::Custom(name) => name
+        }
+    }
+}
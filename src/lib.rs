@@ -2,47 +2,182 @@
 //!
 //!  # Example
 //!
-//!  ```rust
+//!  ```rust,no_run
+//! # #[cfg(not(windows))]
+//! # fn main() {}
 //! #[cfg(windows)]
 //! fn main() {
 //!     embedinator::ResourceBuilder::from_env()
-//!         .add_manifest(std::fs::read_to_string("assets/app.manifest").unwrap())
-//!         .add_icon(32512, Icon::from_png_bytes(std::fs::read("app.png").unwrap()))
+//!         .add_manifest_path("assets/app.manifest")
+//!         .add_icon_path(32512, "app.png")
 //!         .finish();
-//!     println!("cargo:rerun-if-changed=app.manifest");
-//!     println!("cargo:rerun-if-changed=app.png");
 //! }
 //!  ```
 //!
+//!  # Dynamic libraries
+//!  Resources of a `cdylib` have to be linked into the library itself, which needs `cargo:rustc-link-arg-cdylib`
+//!  instead of the `cargo:rustc-link-arg-bins` used for executables. [`ResourceBuilder::from_env`] detects packages that
+//!  only build a `cdylib` and sets everything up for them. Otherwise use [`ResourceBuilder::for_dll`]:
+//!
+//!  ```rust,no_run
+//! # #[cfg(not(windows))]
+//! # fn main() {}
+//! #[cfg(windows)]
+//! fn main() {
+//!     embedinator::ResourceBuilder::from_env()
+//!         .for_dll()
+//!         .add_manifest_path("assets/library.manifest")
+//!         .finish();
+//! }
+//!  ```
+//!
+//!  This links the resources into the library, marks the version info as belonging to a DLL and embeds the manifest
+//!  with the id Windows expects for libraries.
+//!
 //!  # Limitations
-//!  Currently always sets the language to 0x0409 (English, US) as I don't fully understand how multilingual resource files are supposed to look like.
+//!  Resources are added in English (US, 0x0409) unless a language is given explicitly, except for icons and manifests,
+//!  which are language neutral (0x0000) as they don't contain any text.
 
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet};
 use std::env::var;
-use std::path::Path;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::num::ParseIntError;
+use std::ops::{BitOr, BitOrAssign};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+pub use crate::accelerator::{AccelFlags, Accelerator};
+use crate::binary::VersionResource;
 use crate::coff::CoffWriter;
-#[doc(hidden)]
-pub use crate::coff::TargetType;
-use crate::res::ResWriter;
+pub use crate::coff::{ParseTargetError, RelocationType, TargetEnv, TargetType};
+pub use crate::dialog::{ControlClass, DialogControl, DialogFont, DialogTemplate};
+pub use crate::manifest::{DpiAwareness, ExecutionLevel, ManifestBuilder, ManifestError, WindowsVersion};
+use crate::res::{ResHeader, ResWriter};
 
+mod accelerator;
 mod binary;
 mod coff;
+mod dialog;
+mod manifest;
+pub mod parse;
+mod rc;
 mod res;
+#[cfg(test)]
+mod tests;
+
+/// The type of a resource, e.g. `RT_ICON`.
+///
+/// Types are compared by their [id](ResourceType::id), or by their [name](ResourceType::name) for named types,
+/// so [`ResourceType::Custom`] with the id of a standard type is equal to that type.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResourceType {
+    /// The type 0 of the empty resource at the start of every RES file, which can't be used for actual resources.
+    None,
+    /// `RT_VERSION`
+    Version,
+    /// `RT_ICON`, a single image of an icon.
+    Icon,
+    /// `RT_GROUP_ICON`, the directory of the images of an icon.
+    IconGroup,
+    /// `RT_DIALOG`
+    Dialog,
+    /// `RT_ACCELERATOR`
+    Accelerator,
+    /// `RT_HTML`
+    Html,
+    /// `RT_MANIFEST`
+    Manifest,
+    /// `RT_ANICURSOR`
+    AniCursor,
+    /// A type library for COM, which is a named type rather than a numbered one.
+    TypeLib,
+    /// Any other type id. Use [`ResourceType::from_id`] to get the matching variant for the ids of the standard types.
+    Custom(u16)
+}
+
+impl ResourceType {
+    /// The numeric id of the type, which is 0 for the types that are identified by their [name](ResourceType::name) instead.
+    pub fn id(self) -> u16 {
+        match self {
+            ResourceType::None => 0x0,
+            ResourceType::Version => 0x10,
+            ResourceType::Icon => 0x3,
+            ResourceType::IconGroup => 0xE,
+            ResourceType::Dialog => 0x5,
+            ResourceType::Accelerator => 0x9,
+            ResourceType::Html => 0x17,
+            ResourceType::Manifest => 0x18,
+            ResourceType::AniCursor => 0x15,
+            ResourceType::TypeLib => 0x0,
+            ResourceType::Custom(id) => id
+        }
+    }
+
+    /// The name of the type for types that are identified by a string.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            ResourceType::TypeLib => Some("TYPELIB"),
+            _ => None
+        }
+    }
+
+    /// The type with the numeric id, which is [`ResourceType::Custom`] for all ids without a variant of their own.
+    pub fn from_id(id: u16) -> Self {
+        [
+            ResourceType::None,
+            ResourceType::Version,
+            ResourceType::Icon,
+            ResourceType::IconGroup,
+            ResourceType::Dialog,
+            ResourceType::Accelerator,
+            ResourceType::Html,
+            ResourceType::Manifest,
+            ResourceType::AniCursor
+        ]
+        .into_iter()
+        .find(|ty| ty.id() == id)
+        .unwrap_or(ResourceType::Custom(id))
+    }
+}
+
+// Resource directories must be sorted with the named entries first, followed by the ids, so all comparisons are based on that order
+impl PartialEq for ResourceType {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ResourceType {}
+
+impl PartialOrd for ResourceType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ResourceType {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |ty: &Self| (ty.name().is_none(), ty.name(), ty.id());
+        key(self).cmp(&key(other))
+    }
+}
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
-#[repr(u16)]
-pub(crate) enum ResourceType {
-    None = 0x0,
-    Version = 0x10,
-    Icon = 0x3,
-    IconGroup = 0xE,
-    Manifest = 0x18
+impl Display for ResourceType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{}", self.id())
+        }
+    }
 }
 
 impl From<ResourceType> for u32 {
     fn from(value: ResourceType) -> Self {
-        value as u32
+        value.id() as u32
     }
 }
 
@@ -54,28 +189,122 @@ impl ResourceType {
         const PRELOAD: u16 = 0x0040;
         const DISCARDABLE: u16 = 0x1000;
 
+        const RT_CURSOR: u16 = 0x1;
+        const RT_MENU: u16 = 0x4;
+        const RT_STRING: u16 = 0x6;
+        const RT_GROUP_CURSOR: u16 = 0xC;
+
         match self {
             ResourceType::None => 0x0,
             ResourceType::Version => MOVEABLE | PURE,
             ResourceType::Icon => DISCARDABLE | MOVEABLE,
             ResourceType::IconGroup => DISCARDABLE | MOVEABLE | PURE,
-            ResourceType::Manifest => MOVEABLE | PURE
+            ResourceType::Dialog => DISCARDABLE | MOVEABLE | PURE,
+            ResourceType::Accelerator => MOVEABLE | PURE,
+            ResourceType::Html => MOVEABLE | PURE,
+            ResourceType::Manifest => MOVEABLE | PURE,
+            ResourceType::AniCursor => MOVEABLE | PURE,
+            ResourceType::TypeLib => MOVEABLE | PURE,
+            // the standard types that don't have their own variant yet, with the flags used by rc.exe
+            ResourceType::Custom(RT_CURSOR) => DISCARDABLE | MOVEABLE,
+            ResourceType::Custom(RT_MENU | RT_STRING | RT_GROUP_CURSOR) => DISCARDABLE | MOVEABLE | PURE,
+            ResourceType::Custom(_) => MOVEABLE | PURE
         }
     }
 }
 
+/// A Windows language identifier (`LANGID`), combining a primary language with a sublanguage.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(transparent)]
+pub struct LanguageId(pub u16);
+
+impl LanguageId {
+    /// `LANG_NEUTRAL`, the language of icons and manifests that are added without an explicit language,
+    /// as they look the same in every language.
+    pub const NEUTRAL: Self = Self(0x0000);
+    /// English (United States), the language of all resources except icons and manifests that are added without an explicit language.
+    pub const LANG_US: Self = Self(0x0409);
+}
+
+impl From<LanguageId> for u32 {
+    fn from(id: LanguageId) -> u32 {
+        id.0 as u32
+    }
+}
+
 /// The type of the file.
-/// The specification defines even more formats, that could be added in the future if needed.
+/// Drivers and fonts should also set their kind with [`ResourceBuilder::set_file_subtype`].
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum FileType {
+    /// `VFT_UNKNOWN`
+    Unknown = 0,
+    /// `VFT_APP`
     #[default]
     Exe = 1,
-    Dll = 2
+    /// `VFT_DLL`
+    Dll = 2,
+    /// `VFT_DRV`
+    Driver = 3,
+    /// `VFT_FONT`
+    Font = 4,
+    /// `VFT_VXD`
+    Vxd = 5,
+    /// `VFT_STATIC_LIB`
+    StaticLib = 7
+}
+
+/// The operating system the file was designed for.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u32)]
+pub enum FileOs {
+    /// `VOS_UNKNOWN`
+    Unknown = 0x00000000,
+    /// `VOS_DOS`
+    Dos = 0x00010000,
+    /// `VOS_NT`
+    Nt = 0x00040000,
+    /// `VOS__WINDOWS16`
+    Windows16 = 0x00000001,
+    /// `VOS__WINDOWS32`
+    Windows32 = 0x00000004,
+    /// `VOS_DOS_WINDOWS16`
+    DosWindows16 = 0x00010001,
+    /// `VOS_DOS_WINDOWS32`
+    DosWindows32 = 0x00010004,
+    /// `VOS_NT_WINDOWS32`
+    #[default]
+    NtWindows32 = 0x00040004
+}
+
+/// The kind of crate target the resources are linked into.
+///
+/// Static libraries (`staticlib`/`rlib`) are never linked by cargo directly and can therefore not carry resources.
+/// Add the resources to the final binary or dynamic library instead.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CrateType {
+    /// Binary targets, linked using `cargo:rustc-link-arg-bins`.
+    Bin,
+    /// Dynamic libraries (`cdylib`), linked using `cargo:rustc-link-arg-cdylib`.
+    Cdylib
+}
+
+impl CrateType {
+    fn link_arg_directive(self) -> &'static str {
+        match self {
+            CrateType::Bin => "rustc-link-arg-bins",
+            CrateType::Cdylib => "rustc-link-arg-cdylib"
+        }
+    }
 }
 
 /// A version number.
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     pub major: u16,
     pub minor: u16,
@@ -89,8 +318,68 @@ impl Version {
     }
 }
 
+/// Parses a version of the form `major[.minor[.patch[.build]]]`.
+/// Missing trailing components default to 0.
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = [0u16; 4];
+        let mut parts = s.split('.');
+        for (i, component) in components.iter_mut().enumerate() {
+            match parts.next() {
+                Some(part) => {
+                    *component = part
+                        .parse()
+                        .map_err(|error| ParseVersionError::InvalidComponent {
+                            index: i,
+                            component: part.to_string(),
+                            error
+                        })?
+                }
+                None => break
+            }
+        }
+        if parts.next().is_some() {
+            return Err(ParseVersionError::TooManyComponents);
+        }
+        let [major, minor, patch, build] = components;
+        Ok(Self { major, minor, patch, build })
+    }
+}
+
+/// The error returned when parsing a [`Version`] from a string fails.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseVersionError {
+    /// The version has more than four dot-separated components.
+    TooManyComponents,
+    /// A component is not a number or does not fit into a `u16`.
+    InvalidComponent { index: usize, component: String, error: ParseIntError }
+}
+
+impl Display for ParseVersionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseVersionError::TooManyComponents => write!(f, "a version can have at most four components"),
+            ParseVersionError::InvalidComponent { index, component, error } => {
+                write!(f, "invalid version component {index} ({component:?}): {error}")
+            }
+        }
+    }
+}
+
+impl Error for ParseVersionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseVersionError::TooManyComponents => None,
+            ParseVersionError::InvalidComponent { error, .. } => Some(error)
+        }
+    }
+}
+
 /// Flags that indicate the file's status.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum FileFlag {
     /// The file contains debugging information or is compiled with debugging features enabled.
@@ -107,88 +396,836 @@ pub enum FileFlag {
     SpecialBuild = 0x20 //InfoInferred,
 }
 
+impl FileFlag {
+    const ALL: [FileFlag; 5] = [
+        FileFlag::Debug,
+        FileFlag::Prerelease,
+        FileFlag::Patched,
+        FileFlag::PrivateBuild,
+        FileFlag::SpecialBuild
+    ];
+
+    /// The version string that has to accompany the flag, if any.
+    pub fn required_string(self) -> Option<&'static str> {
+        match self {
+            FileFlag::PrivateBuild => Some("PrivateBuild"),
+            FileFlag::SpecialBuild => Some("SpecialBuild"),
+            _ => None
+        }
+    }
+}
+
+/// A set of [`FileFlag`]s, created by combining flags with `|`.
+///
+/// ```
+/// # use embedinator::{FileFlag, ResourceBuilder};
+/// ResourceBuilder::default().add_file_flags(FileFlag::Debug | FileFlag::Prerelease);
+/// ```
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FileFlags(u8);
+
+impl FileFlags {
+    pub fn contains(self, flag: FileFlag) -> bool {
+        self.0 & flag as u8 != 0
+    }
+
+    /// Checks that the version strings contain the entries that some flags require, see [`FileFlag::required_string`].
+    pub fn validate(self, strings: &BTreeMap<String, String>) -> Result<(), EmbedError> {
+        match self.into_iter().find_map(|flag| {
+            flag.required_string()
+                .filter(|key| !strings.contains_key(*key))
+                .map(|key| (flag, key))
+        }) {
+            Some((flag, key)) => Err(EmbedError::MissingFlagString { flag, key }),
+            None => Ok(())
+        }
+    }
+}
+
+impl From<FileFlag> for FileFlags {
+    fn from(flag: FileFlag) -> Self {
+        Self(flag as u8)
+    }
+}
+
+impl<const N: usize> From<[FileFlag; N]> for FileFlags {
+    fn from(flags: [FileFlag; N]) -> Self {
+        flags.into_iter().collect()
+    }
+}
+
+impl From<Vec<FileFlag>> for FileFlags {
+    fn from(flags: Vec<FileFlag>) -> Self {
+        flags.into_iter().collect()
+    }
+}
+
+impl From<BTreeSet<FileFlag>> for FileFlags {
+    fn from(flags: BTreeSet<FileFlag>) -> Self {
+        flags.into_iter().collect()
+    }
+}
+
+impl BitOr<FileFlag> for FileFlag {
+    type Output = FileFlags;
+
+    fn bitor(self, rhs: FileFlag) -> Self::Output {
+        FileFlags::from(self) | rhs
+    }
+}
+
+impl<T: Into<FileFlags>> BitOr<T> for FileFlags {
+    type Output = FileFlags;
+
+    fn bitor(self, rhs: T) -> Self::Output {
+        Self(self.0 | rhs.into().0)
+    }
+}
+
+impl<T: Into<FileFlags>> BitOrAssign<T> for FileFlags {
+    fn bitor_assign(&mut self, rhs: T) {
+        self.0 |= rhs.into().0;
+    }
+}
+
+impl FromIterator<FileFlag> for FileFlags {
+    fn from_iter<I: IntoIterator<Item = FileFlag>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::default(), |flags, flag| flags | flag)
+    }
+}
+
+impl IntoIterator for FileFlags {
+    type Item = FileFlag;
+    type IntoIter = std::vec::IntoIter<FileFlag>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        FileFlag::ALL
+            .into_iter()
+            .filter(|flag| self.contains(*flag))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// The version info resource, as shown on the details tab of the file properties.
+///
+/// [`ResourceBuilder`] fills one of these for [`LanguageId::LANG_US`] through its setters,
+/// versions for other languages can be added with [`ResourceBuilder::add_version_for_language`].
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
-struct VersionInfo {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VersionInfo {
     pub file_version: Version,
     pub product_version: Version,
     pub file_type: FileType,
+    pub file_subtype: u32,
+    pub file_os: FileOs,
     pub flags: BTreeSet<FileFlag>,
-    pub strings: BTreeMap<String, String>
+    pub strings: BTreeMap<String, String>,
+    /// The code page of the strings, `None` for Unicode.
+    pub codepage: Option<u16>,
+    /// The bits of `flags` that are valid, `None` for all flags defined by [`FileFlag`] (`0x3f`).
+    pub flags_mask: Option<u32>,
+    /// The creation date of the file as a `FILETIME`, in 100-nanosecond intervals since January 1, 1601 (UTC).
+    pub file_date: u64
+}
+
+impl VersionInfo {
+    /// Whether the version info has neither a version number nor any strings, so there is nothing worth showing.
+    /// Such a version info is left out of the resources.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty() && self.file_version == Version::default() && self.product_version == Version::default()
+    }
 }
 
 /// An Icon resource.
+///
+/// Icons hash by their [content hash](Icon::content_hash), so they can be used as cheap keys for caches.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Icon {
+    data: Vec<u8>,
+    hash: u64,
+    width: u32,
+    height: u32,
+    bit_count: u16,
+    source: Option<IconSource>
+}
+
+/// The file an [`Icon`] was loaded from, which is all that gets serialized.
 #[derive(Clone, Eq, PartialEq)]
-pub struct Icon(Vec<u8>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(untagged))]
+enum IconSource {
+    /// A PNG file, see [`Icon::from_png_file`]. Serialized as the bare path.
+    Png(PathBuf),
+    /// One image of an `.ico` file, see [`Icon::from_ico_file`].
+    Ico { ico: PathBuf, index: usize },
+    /// The DIB copy of a PNG icon added by [`ResourceBuilder::require_bmp_fallback`].
+    #[cfg(feature = "image")]
+    BmpFallback { bmp_fallback: Box<IconSource> }
+}
+
+impl IconSource {
+    #[cfg(feature = "serde")]
+    fn load(&self) -> std::io::Result<Icon> {
+        match self {
+            IconSource::Png(path) => Icon::from_png_file(path),
+            IconSource::Ico { ico, index } => Icon::from_ico_file(ico)?
+                .into_iter()
+                .nth(*index)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("the file has no image {index}"))),
+            #[cfg(feature = "image")]
+            IconSource::BmpFallback { bmp_fallback } => bmp_fallback
+                .load()?
+                .png_to_dib()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        }
+    }
+}
+
+impl Display for IconSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IconSource::Png(path) => write!(f, "{}", path.display()),
+            IconSource::Ico { ico, index } => write!(f, "image {index} of {}", ico.display()),
+            #[cfg(feature = "image")]
+            IconSource::BmpFallback { bmp_fallback } => write!(f, "BMP fallback of {bmp_fallback}")
+        }
+    }
+}
+
+impl Hash for Icon {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
 
 impl Icon {
-    /// Create an icon from a PNG file. The PNG must contain 32bpp RGBA data.
-    /// Other icon format are not currently not supported, but could be added in the future
+    /// The largest width and height an icon can have.
+    pub const MAX_SIZE: u32 = 256;
+
+    /// Create an icon from a PNG file. The PNG must contain non-interlaced 8-bit RGBA or RGB data, the latter is treated as opaque.
+    /// Images with fewer colors can be embedded as DIBs with [`Icon::from_dib_bytes`].
+    ///
+    /// # Panics
+    /// Panics if the PNG is not supported, see [`Icon::try_from_png_bytes`].
     pub fn from_png_bytes(data: Vec<u8>) -> Self {
-        assert_eq!(&data[..8], &[137, 80, 78, 71, 13, 10, 26, 10], "Invalid PNG file");
-        assert_eq!(&data[12..16], b"IHDR", "Invalid PNG file");
-        // let width = u32::from_be_bytes((&data[16..20]).try_into().unwrap());
-        // let height = u32::from_be_bytes((&data[20..24]).try_into().unwrap());
+        Self::try_from_png_bytes(data).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`Icon::from_png_bytes`], but returns an error if the PNG is not supported.
+    pub fn try_from_png_bytes(data: Vec<u8>) -> Result<Self, IconError> {
+        const IHDR_LENGTH: [u8; 4] = 13u32.to_be_bytes();
+        if !data.starts_with(&Self::PNG_SIGNATURE) {
+            return Err(IconError::InvalidPng);
+        }
+        // the fields are read at fixed offsets, which only works if the header chunk comes first and has its usual size
+        if data.len() < 29 || data[8..12] != IHDR_LENGTH || &data[12..16] != b"IHDR" {
+            return Err(IconError::InvalidPngHeader);
+        }
+        let width = u32::from_be_bytes(data[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(data[20..24].try_into().unwrap());
         let bit_depth = data[24];
         let color_type = data[25];
-        assert_eq!((color_type, bit_depth), (6, 8), "The png must contain 32bpp RGBA data");
-        Self(data)
+        let interlace_method = data[28];
+        let bit_count = match (color_type, bit_depth) {
+            (6, 8) => 32,
+            (2, 8) => 24,
+            _ => return Err(IconError::UnsupportedFormat { color_type, bit_depth })
+        };
+        if interlace_method != 0 {
+            return Err(IconError::Interlaced);
+        }
+        Self::check_size(width, height)?;
+        Ok(Self {
+            hash: content_hash(&data),
+            data,
+            width,
+            height,
+            bit_count,
+            source: None
+        })
+    }
+
+    /// Create an icon from 8-bit RGBA pixels in rows from top to bottom, stored as an uncompressed 32bpp DIB.
+    ///
+    /// The AND mask is generated from the alpha channel. Current versions of Windows ignore it in favor of the alpha channel,
+    /// but older ones need it to draw fully transparent pixels as transparent.
+    ///
+    /// # Panics
+    /// Panics if the pixels don't match the size or the icon is too large, see [`Icon::try_from_rgba`].
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Self {
+        Self::try_from_rgba(width, height, rgba).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`Icon::from_rgba`], but returns an error if the pixels don't match the size or the icon is too large.
+    pub fn try_from_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<Self, IconError> {
+        Self::check_size(width, height)?;
+        let color_size = width as usize * height as usize * 4;
+        if rgba.len() != color_size {
+            return Err(IconError::PixelCountMismatch {
+                expected: color_size,
+                actual: rgba.len()
+            });
+        }
+        // every row of the mask is padded to a multiple of four bytes, the 32bpp color rows always are
+        let mask_stride = (width as usize).div_ceil(32) * 4;
+        let mut data = Vec::with_capacity(40 + color_size + mask_stride * height as usize);
+        data.extend_from_slice(&40u32.to_le_bytes()); // biSize
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&(height * 2).to_le_bytes()); // the height includes the AND mask
+        data.extend_from_slice(&1u16.to_le_bytes()); // biPlanes
+        data.extend_from_slice(&32u16.to_le_bytes()); // biBitCount
+        data.extend_from_slice(&0u32.to_le_bytes()); // biCompression (BI_RGB)
+        data.extend_from_slice(&((color_size + mask_stride * height as usize) as u32).to_le_bytes()); // biSizeImage
+        data.extend_from_slice(&[0; 16]); // resolution and palette size
+
+        // both the color bits and the mask are stored bottom-up
+        let rows = || rgba.chunks_exact((width as usize * 4).max(1)).rev();
+        for row in rows() {
+            for pixel in row.chunks_exact(4) {
+                data.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            }
+        }
+        for row in rows() {
+            let mut mask = vec![0u8; mask_stride];
+            for (x, pixel) in row.chunks_exact(4).enumerate() {
+                if pixel[3] == 0 {
+                    mask[x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            data.extend_from_slice(&mask);
+        }
+        Self::try_from_dib_bytes(data)
+    }
+
+    /// Create an icon from an uncompressed device-independent bitmap, the format of the images in classic `.ico` files.
+    /// The data starts with a `BITMAPINFOHEADER` with twice the height of the icon, followed by the color table,
+    /// the color bits and the AND mask. Paletted images keep their bit count, so Windows can pick them for low-color displays.
+    ///
+    /// # Panics
+    /// Panics if the bitmap is not supported, see [`Icon::try_from_dib_bytes`].
+    pub fn from_dib_bytes(data: Vec<u8>) -> Self {
+        Self::try_from_dib_bytes(data).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`Icon::from_dib_bytes`], but returns an error if the bitmap is not supported.
+    pub fn try_from_dib_bytes(data: Vec<u8>) -> Result<Self, IconError> {
+        const BI_RGB: u32 = 0;
+        let u16_at = |i: usize| u16::from_le_bytes(data[i..i + 2].try_into().unwrap());
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        if data.len() < 40 || u32_at(0) < 40 || u16_at(12) != 1 || u32_at(16) != BI_RGB {
+            return Err(IconError::InvalidDib);
+        }
+        let header_size = u32_at(0) as usize;
+        let width = u32_at(4);
+        // the height covers both the color bits and the AND mask
+        let height = u32_at(8) / 2;
+        let bit_count = u16_at(14);
+        let colors = match (bit_count, u32_at(32)) {
+            (1 | 4 | 8, 0) => 1 << bit_count,
+            (1 | 4 | 8, used) => used as usize,
+            (16 | 24 | 32, _) => 0,
+            _ => return Err(IconError::InvalidDib)
+        };
+        if width == 0 || height == 0 {
+            return Err(IconError::InvalidDib);
+        }
+        Self::check_size(width, height)?;
+        // the size is limited now, but the header and palette sizes come straight from the file
+        let stride = |bits: usize| (width as usize * bits).div_ceil(32) * 4;
+        let size = colors
+            .checked_mul(4)
+            .and_then(|palette| palette.checked_add(header_size))
+            .and_then(|size| size.checked_add((stride(bit_count as usize) + stride(1)) * height as usize))
+            .ok_or(IconError::InvalidDib)?;
+        if data.len() < size {
+            return Err(IconError::InvalidDib);
+        }
+        Ok(Self {
+            hash: content_hash(&data),
+            data,
+            width,
+            height,
+            bit_count,
+            source: None
+        })
+    }
+
+    /// Load an icon from a PNG file. See [`Icon::from_png_bytes`] for the supported formats.
+    /// Unsupported PNGs are reported as [`std::io::ErrorKind::InvalidData`] wrapping an [`IconError`].
+    pub fn from_png_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let icon = Self::try_from_png_bytes(std::fs::read(path)?).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        Ok(Self {
+            source: Some(IconSource::Png(path.to_path_buf())),
+            ..icon
+        })
+    }
+
+    /// Reads all images of an `.ico` file, which can be combined into a single group using [`ResourceBuilder::add_icon_group`].
+    /// Every image is either a PNG or a DIB and has to be supported by [`Icon::try_from_png_bytes`] or [`Icon::try_from_dib_bytes`].
+    ///
+    /// # Panics
+    /// Panics if the file or one of its images is not supported, see [`Icon::try_from_ico_bytes`].
+    pub fn from_ico_bytes(data: &[u8]) -> Vec<Self> {
+        Self::try_from_ico_bytes(data).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Like [`Icon::from_ico_bytes`], but returns an error if the file or one of its images is not supported.
+    pub fn try_from_ico_bytes(data: &[u8]) -> Result<Vec<Self>, IconError> {
+        const ICONDIR_SIZE: usize = 6;
+        const ICONDIRENTRY_SIZE: usize = 16;
+        let u16_at = |i: usize| {
+            data.get(i..i + 2)
+                .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+        };
+        let u32_at = |i: usize| {
+            data.get(i..i + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        };
+        // reserved, type (1 for icons, 2 for cursors) and the number of images
+        let count = match (u16_at(0), u16_at(2), u16_at(4)) {
+            (Some(0), Some(1), Some(count)) if count > 0 => count as usize,
+            _ => return Err(IconError::InvalidIco)
+        };
+        (0..count)
+            .map(|i| {
+                let entry = ICONDIR_SIZE + i * ICONDIRENTRY_SIZE;
+                let (size, offset) = u32_at(entry + 8)
+                    .zip(u32_at(entry + 12))
+                    .ok_or(IconError::InvalidIco)?;
+                let image = data
+                    .get(offset..offset.saturating_add(size))
+                    .ok_or(IconError::InvalidIco)?;
+                match image.starts_with(&Self::PNG_SIGNATURE) {
+                    true => Self::try_from_png_bytes(image.to_vec()),
+                    false => Self::try_from_dib_bytes(image.to_vec())
+                }
+            })
+            .collect()
+    }
+
+    /// Load all images of an `.ico` file. See [`Icon::from_ico_bytes`] for the supported files.
+    /// Unsupported files are reported as [`std::io::ErrorKind::InvalidData`] wrapping an [`IconError`].
+    pub fn from_ico_file<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<Self>> {
+        let path = path.as_ref();
+        let icons = Self::try_from_ico_bytes(&std::fs::read(path)?).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        Ok(icons
+            .into_iter()
+            .enumerate()
+            .map(|(index, icon)| Self {
+                source: Some(IconSource::Ico {
+                    ico: path.to_path_buf(),
+                    index
+                }),
+                ..icon
+            })
+            .collect())
+    }
+
+    /// The width and height of the icon in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// A hash of the encoded PNG or DIB data, computed once when the icon is created.
+    ///
+    /// The same image encoded differently hashes differently, as the pixels are never decoded.
+    /// The value is only stable for the same build, so it should not be persisted.
+    pub fn content_hash(&self) -> u64 {
+        self.hash
+    }
+
+    const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+    /// Compares the data of two icons, using the hashes to skip the full comparison for most different icons.
+    fn same_content(&self, other: &Icon) -> bool {
+        self.hash == other.hash && self.data == other.data
+    }
+
+    /// Icon directories store the dimensions in a single byte, so larger images can't be described.
+    fn check_size(width: u32, height: u32) -> Result<(), IconError> {
+        if width > Self::MAX_SIZE || height > Self::MAX_SIZE {
+            return Err(IconError::TooLarge { width, height });
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a PNG can't be used as an [`Icon`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IconError {
+    /// The data does not start with a PNG signature.
+    InvalidPng,
+    /// The PNG signature is not followed by a 13-byte `IHDR` chunk, which has to be the first chunk of every PNG.
+    InvalidPngHeader,
+    /// The PNG uses Adam7 interlacing, which Windows does not support in icons.
+    Interlaced,
+    /// The PNG is neither 8-bit RGBA nor 8-bit RGB.
+    UnsupportedFormat { color_type: u8, bit_depth: u8 },
+    /// The bitmap passed to [`Icon::from_dib_bytes`] is truncated, compressed or has an unusual bit count.
+    InvalidDib,
+    /// The data passed to [`Icon::from_ico_bytes`] is not an `.ico` file with at least one image, or is truncated.
+    InvalidIco,
+    /// The image is larger than [`Icon::MAX_SIZE`] in at least one dimension.
+    TooLarge { width: u32, height: u32 },
+    /// The pixels passed to [`Icon::from_rgba`] don't match its size, `expected` and `actual` are in bytes.
+    PixelCountMismatch { expected: usize, actual: usize },
+    /// The PNG has a valid header, but its image data can't be decoded.
+    /// This is only noticed when the pixels are needed, like for [`ResourceBuilder::require_bmp_fallback`].
+    CorruptPng
+}
+
+impl Display for IconError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IconError::InvalidPng => write!(f, "invalid PNG file"),
+            IconError::InvalidPngHeader => write!(f, "invalid PNG file, the first chunk is not a 13-byte IHDR chunk"),
+            IconError::Interlaced => write!(f, "interlaced PNGs are not supported in icons"),
+            IconError::InvalidDib => write!(f, "invalid or unsupported icon bitmap"),
+            IconError::InvalidIco => write!(f, "invalid or truncated .ico file"),
+            IconError::CorruptPng => write!(f, "the image data of the PNG is corrupt"),
+            IconError::PixelCountMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes of RGBA pixels, but got {actual}")
+            }
+            IconError::TooLarge { width, height } => write!(
+                f,
+                "the image is {width}x{height} pixels, but icons can be at most {0}x{0} pixels, downscale it first",
+                Icon::MAX_SIZE
+            ),
+            IconError::UnsupportedFormat { color_type, bit_depth } => {
+                let color = match color_type {
+                    0 => "grayscale",
+                    2 => "RGB",
+                    3 => "paletted",
+                    4 => "grayscale with alpha",
+                    6 => "RGBA",
+                    _ => "unknown"
+                };
+                write!(
+                    f,
+                    "the PNG contains {bit_depth}-bit {color} data (color type {color_type}), but icons must contain 8-bit RGBA or RGB data"
+                )
+            }
+        }
+    }
+}
+
+impl Error for IconError {}
+
+#[cfg(feature = "image")]
+impl Icon {
+    /// The icon sizes commonly used by Windows.
+    pub const STANDARD_SIZES: [u32; 8] = [16, 20, 24, 32, 40, 48, 64, 256];
+
+    /// Create an icon from any image. The image is converted to 32bpp RGBA and stored as PNG.
+    /// Images larger than [`Icon::MAX_SIZE`] are downscaled to fit, keeping their aspect ratio.
+    pub fn from_dynamic_image(mut img: image::DynamicImage) -> Self {
+        if img.width() > Self::MAX_SIZE || img.height() > Self::MAX_SIZE {
+            img = img.resize(Self::MAX_SIZE, Self::MAX_SIZE, image::imageops::FilterType::Lanczos3);
+        }
+        let mut data = Vec::new();
+        img.into_rgba8()
+            .write_with_encoder(image::codecs::png::PngEncoder::new(&mut data))
+            .expect("Failed to encode icon as PNG");
+        Self::from_png_bytes(data)
+    }
+
+    /// Load an icon from any image file format supported by the `image` crate.
+    pub fn from_path_auto<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        Ok(Self::from_dynamic_image(image::open(path)?))
+    }
+
+    /// Create one icon per requested size by downscaling `img`.
+    /// Sizes larger than the source image are skipped, as upscaling would only produce blurry icons.
+    /// The resulting icons can be combined into a single group using [`ResourceBuilder::add_icon_group`].
+    pub fn from_dynamic_image_sizes(img: &image::DynamicImage, sizes: &[u32]) -> Vec<Self> {
+        sizes
+            .iter()
+            .filter(|&&size| size <= img.width().max(img.height()))
+            .map(|&size| Self::from_dynamic_image(img.resize(size, size, image::imageops::FilterType::Lanczos3)))
+            .collect()
+    }
+
+    fn is_png(&self) -> bool {
+        self.data.starts_with(&Self::PNG_SIGNATURE)
+    }
+
+    /// Decodes a PNG icon and stores it as DIB instead, for [`ResourceBuilder::require_bmp_fallback`].
+    fn png_to_dib(&self) -> Result<Self, IconError> {
+        let img = image::load_from_memory_with_format(&self.data, image::ImageFormat::Png).map_err(|_| IconError::CorruptPng)?;
+        Ok(Self {
+            source: self.source.clone().map(|source| IconSource::BmpFallback {
+                bmp_fallback: Box::new(source)
+            }),
+            ..Self::from_dynamic_image_dib(img)
+        })
+    }
+
+    /// Create an icon from any image, stored as an uncompressed 32bpp DIB instead of a PNG, see [`Icon::from_rgba`].
+    /// This is how the small images of `.ico` files are usually stored, as older programs can't read PNG icons.
+    /// Images larger than [`Icon::MAX_SIZE`] are downscaled to fit, like in [`Icon::from_dynamic_image`].
+    pub fn from_dynamic_image_dib(mut img: image::DynamicImage) -> Self {
+        if img.width() > Self::MAX_SIZE || img.height() > Self::MAX_SIZE {
+            img = img.resize(Self::MAX_SIZE, Self::MAX_SIZE, image::imageops::FilterType::Lanczos3);
+        }
+        let img = img.into_rgba8();
+        Self::from_rgba(img.width(), img.height(), img.as_raw())
+    }
+}
+
+/// A resource imported from an existing RES file or added with [`ResourceBuilder::add_raw_resource_full`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ImportedResource {
+    ty: ResourceType,
+    id: u16,
+    language: LanguageId,
+    flags: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    data_version: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    version: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    characteristics: u32,
+    data: Vec<u8>
+}
+
+/// A resource of any type together with all the fields of its header in a RES file,
+/// for [`ResourceBuilder::add_raw_resource_full`].
+///
+/// COFF objects only store the type, id, language and data, so the other fields only end up in
+/// [RES files](ResourceBuilder::compile_to_res). Windows itself ignores them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RawResource {
+    pub type_id: u16,
+    pub id: u16,
+    pub language: LanguageId,
+    /// The memory flags from 16-bit Windows: `MOVEABLE` (0x10), `PURE` (0x20), `PRELOAD` (0x40) and `DISCARDABLE` (0x1000).
+    pub memory_flags: u16,
+    /// The `DataVersion` field, which is reserved and always 0 in files written by `rc.exe`.
+    pub data_version: u32,
+    /// The `Version` field, set by the `VERSION` statement of a resource script. Tools are free to use it.
+    pub version: u32,
+    /// The `Characteristics` field, set by the `CHARACTERISTICS` statement of a resource script. Tools are free to use it.
+    pub characteristics: u32,
+    pub data: Vec<u8>
+}
+
+impl RawResource {
+    /// A resource in English (US) with the header `rc.exe` writes for the type.
+    pub fn new(type_id: u16, id: u16, data: Vec<u8>) -> Self {
+        let header = ResHeader::new(ResourceType::from_id(type_id), LanguageId::LANG_US);
+        Self {
+            type_id,
+            id,
+            language: header.language,
+            memory_flags: header.flags,
+            data_version: header.data_version,
+            version: header.version,
+            characteristics: header.characteristics,
+            data
+        }
+    }
+}
+
+/// Icons are serialized as the file they were loaded from, as embedding the image data would be wasteful.
+/// PNG files are stored as their path, images of `.ico` files as `{ "ico": path, "index": n }`
+/// and the DIB copies of [`ResourceBuilder::require_bmp_fallback`] as `{ "bmp_fallback": source }`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Icon {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.source {
+            Some(source) => source.serialize(serializer),
+            None => Err(serde::ser::Error::custom("only icons loaded from a file can be serialized"))
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Icon {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = IconSource::deserialize(deserializer)?;
+        source
+            .load()
+            .map_err(|err| serde::de::Error::custom(format!("failed to load icon {source}: {err}")))
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct IconGroupEntry {
     icon_id: u16,
-    icon_size: usize
+    icon_size: usize,
+    width: u32,
+    height: u32,
+    bit_count: u16
 }
 
 /// A builder for compiling a new resource file in a cargo build script and setting the correct linker flags.
-#[derive(Default, Clone)]
+///
+/// With the `serde` feature enabled, the builder can also be loaded from a declarative description.
+/// Icons are referenced by their path and are decoded when deserializing.
+#[derive(Default, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ResourceBuilder {
     version: VersionInfo,
-    icon_groups: Vec<(u16, [IconGroupEntry; 1])>,
-    icons: Vec<(u16, Icon)>,
-    manifest: Option<String>
+    localized_versions: BTreeMap<LanguageId, VersionInfo>,
+    icon_groups: Vec<(u16, LanguageId, Vec<IconGroupEntry>)>,
+    icons: Vec<(u16, LanguageId, Icon)>,
+    dialogs: Vec<(u16, DialogTemplate)>,
+    accelerators: Vec<(u16, Vec<Accelerator>)>,
+    raw_resources: Vec<(ResourceType, u16, Vec<u8>)>,
+    named_resources: Vec<(ResourceType, String, Vec<u8>)>,
+    imported: Vec<ImportedResource>,
+    manifest: Option<(LanguageId, Vec<u8>)>,
+    manifest_unchecked: bool,
+    manifest_raw: bool,
+    activation_manifests: Vec<(u16, Vec<u8>)>,
+    icon_id_base: Option<u16>,
+    bmp_fallback: bool,
+    resource_version: (u16, u16),
+    target_env: Option<TargetEnv>,
+    output_name: Option<String>,
+    split_objects: bool,
+    crate_types: BTreeSet<CrateType>,
+    strict: bool,
+    verbose: bool
 }
 
 impl ResourceBuilder {
-    /// Automatically fills many fields with values from environment variables set by cargo.
+    /// Automatically fills many fields with values from environment variables set by cargo:
+    /// - `CARGO_PKG_VERSION` for the file and product version and the `FileVersion` and `ProductVersion` strings.
+    ///   A pre-release version sets [`FileFlag::Prerelease`] and numeric build metadata (`1.2.3+45`) becomes the build component.
+    /// - `CARGO_PKG_NAME` for the `ProductName` string
+    /// - `CARGO_PKG_DESCRIPTION` for the `FileDescription` string, falling back to the package name if it is empty
+    /// - `CARGO_MANIFEST_DIR` to find out whether the package only builds a dynamic library, if the `toml` feature is enabled.
+    ///   In that case the file type is set to [`FileType::Dll`] and a `cdylib` is added with [`ResourceBuilder::add_crate_type`].
+    ///   Use [`ResourceBuilder::set_file_type`] to override this, or to mark a library as such without the feature.
+    /// - `CARGO_PKG_HOMEPAGE` for the `Comments` string
+    /// - `CARGO_PKG_AUTHORS` for the `CompanyName` string, using the name of the first author without the email address
+    /// - `SOURCE_DATE_EPOCH`, if set by a reproducible build, for the file date of the version info.
+    ///
+    /// All of these can be overridden by calling the corresponding setters or [`ResourceBuilder::add_string`] afterward.
+    ///
+    /// # Panics
+    /// Panics if [`ResourceBuilder::try_from_env`] fails.
     pub fn from_env() -> Self {
-        println!("cargo:rerun-if-env-changed=CARGO_PKG_VERSION_MAJOR");
-        println!("cargo:rerun-if-env-changed=CARGO_PKG_VERSION_MINOR");
-        println!("cargo:rerun-if-env-changed=CARGO_PKG_VERSION_PATCH");
-        println!("cargo:rerun-if-env-changed=CARGO_PKG_VERSION");
-        println!("cargo:rerun-if-env-changed=CARGO_PKG_NAME");
-        println!("cargo:rerun-if-env-changed=CARGO_PKG_DESCRIPTION");
+        Self::try_from_env().unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        let version = Version {
-            major: var("CARGO_PKG_VERSION_MAJOR")
-                .expect("No CARGO_PKG_VERSION_MAJOR env var")
-                .parse()
-                .unwrap_or(0),
-            minor: var("CARGO_PKG_VERSION_MINOR")
-                .expect("No CARGO_PKG_VERSION_MINOR env var")
-                .parse()
-                .unwrap_or(0),
-            patch: var("CARGO_PKG_VERSION_PATCH")
-                .expect("No CARGO_PKG_VERSION_PATCH env var")
-                .parse()
-                .unwrap_or(0),
-            build: 0
-        };
-        Self::default()
+    /// Like [`ResourceBuilder::from_env`], but returns an error if `CARGO_PKG_VERSION` or `CARGO_PKG_NAME` is missing,
+    /// or if a component of the version doesn't fit into the 16 bits the version info has for it.
+    pub fn try_from_env() -> Result<Self, EmbedError> {
+        let package = PackageEnv::read();
+        let version = package
+            .version
+            .as_deref()
+            .ok_or(EmbedError::MissingEnvVar("CARGO_PKG_VERSION"))?;
+        let name = package
+            .name
+            .as_deref()
+            .ok_or(EmbedError::MissingEnvVar("CARGO_PKG_NAME"))?;
+        Ok(Self::from_package(version, name, package.description.clone())?.set_package_details(&package))
+    }
+
+    /// Like [`ResourceBuilder::from_env`], but never fails.
+    /// Missing variables are replaced by version `0.0.0` and package name `app`.
+    /// Versions that don't fit into the version info are replaced by `0.0.0` as well, with a cargo warning.
+    pub fn from_env_or_default() -> Self {
+        let package = PackageEnv::read();
+        let name = package.name.as_deref().unwrap_or("app");
+        Self::from_package(package.version.as_deref().unwrap_or("0.0.0"), name, package.description.clone())
+            .unwrap_or_else(|err| {
+                println!("cargo:warning={err}, using 0.0.0 instead");
+                Self::from_package("0.0.0", name, package.description.clone()).expect("0.0.0 is a valid version")
+            })
+            .set_package_details(&package)
+    }
+
+    /// Creates a builder that only embeds `version`, for tools that just stamp a version onto a binary.
+    ///
+    /// This is a shorthand for setting the version info on an empty builder, the compiled object is the same
+    /// and contains nothing but the `RT_VERSION` resource.
+    pub fn version_only(version: VersionInfo) -> Self {
+        Self { version, ..Self::default() }
+    }
+
+    /// Applies everything besides the version and the names, which have defaults in [`ResourceBuilder::from_env_or_default`].
+    fn set_package_details(mut self, package: &PackageEnv) -> Self {
+        if let Some(homepage) = &package.homepage {
+            self = self.add_string("Comments", homepage);
+        }
+        if let Some(author) = package.authors.as_deref().and_then(first_author) {
+            self = self.add_string("CompanyName", author);
+        }
+        self.set_library_kind(package.library)
+            .set_source_date(package.source_date_epoch)
+    }
+
+    fn set_source_date(self, unix_seconds: Option<u64>) -> Self {
+        // FILETIME counts from 1601 instead of 1970
+        const UNIX_EPOCH_FILETIME_SECONDS: u64 = 11_644_473_600;
+        let filetime = unix_seconds.map(|seconds| {
+            seconds
+                .checked_add(UNIX_EPOCH_FILETIME_SECONDS)
+                .and_then(|seconds| seconds.checked_mul(10_000_000))
+        });
+        match filetime {
+            Some(Some(filetime)) => self.set_version_timestamp(filetime),
+            Some(None) => {
+                println!("cargo:warning=SOURCE_DATE_EPOCH is too far in the future for the version info, ignoring it");
+                self
+            }
+            None => self
+        }
+    }
+
+    fn set_library_kind(self, library: Option<LibraryKind>) -> Self {
+        match library {
+            Some(LibraryKind::Cdylib) => self.add_crate_type(CrateType::Cdylib),
+            Some(LibraryKind::Dylib) => self.set_file_type(FileType::Dll),
+            None => self
+        }
+    }
+
+    fn from_package(version_text: &str, name: &str, description: Option<String>) -> Result<Self, EmbedError> {
+        // semver: major.minor.patch[-prerelease][+build]
+        let (version_core, build) = version_text
+            .split_once('+')
+            .map_or((version_text, None), |(core, build)| (core, Some(build)));
+        let (version_core, prerelease) = version_core
+            .split_once('-')
+            .map_or((version_core, None), |(core, pre)| (core, Some(pre)));
+        let mut version: Version = version_core
+            .parse()
+            .map_err(|error| EmbedError::InvalidPackageVersion {
+                version: version_text.to_string(),
+                error
+            })?;
+        // the fourth component is the only place for build metadata, but it only fits if it is a plain number
+        if let Some(build) = build.and_then(|b| b.parse().ok()) {
+            version.build = build;
+        }
+        let flags: FileFlags = prerelease
+            .map(|_| FileFlag::Prerelease)
+            .into_iter()
+            .collect();
+        Ok(Self::default()
+            .add_file_flags(flags)
             .set_file_version(version)
             .set_product_version(version)
-            .add_string("FileVersion", var("CARGO_PKG_VERSION").expect("No CARGO_PKG_VERSION env var"))
-            .add_string("ProductVersion", var("CARGO_PKG_VERSION").expect("No CARGO_PKG_VERSION env var"))
-            .add_string("ProductName", var("CARGO_PKG_NAME").expect("No CARGO_PKG_NAME env var"))
+            .add_string("FileVersion", version_text)
+            .add_string("ProductVersion", version_text)
+            .add_string("ProductName", name)
             .add_string(
                 "FileDescription",
-                var("CARGO_PKG_DESCRIPTION")
-                    .ok()
+                description
                     .filter(|d| !d.is_empty())
-                    .or_else(|| var("CARGO_PKG_NAME").ok())
-                    .expect("No CARGO_PKG_DESCRIPTION or CARGO_PKG_NAME env var")
-            )
+                    .unwrap_or_else(|| name.to_string())
+            ))
     }
 
     pub fn set_file_version(mut self, version: Version) -> Self {
@@ -201,58 +1238,896 @@ impl ResourceBuilder {
         self
     }
 
+    /// Sets the build component of both the file and the product version.
+    ///
+    /// If the `FileVersion` and `ProductVersion` strings are set, their fourth component is replaced (or added) as well,
+    /// e.g. `1.2.3-rc1` becomes `1.2.3.45-rc1`. Strings that don't start with a version number are left untouched.
+    pub fn set_build_number(mut self, build: u16) -> Self {
+        self.version.file_version.build = build;
+        self.version.product_version.build = build;
+        for key in ["FileVersion", "ProductVersion"] {
+            if let Some(text) = self.version.strings.get_mut(key) {
+                *text = with_build_number(text, build);
+            }
+        }
+        self
+    }
+
+    /// Sets up the builder for a dynamic library, which is the file type [`FileType::Dll`] and the crate type [`CrateType::Cdylib`].
+    ///
+    /// The manifest of a DLL uses the id 2 (`ISOLATIONAWARE_MANIFEST_RESOURCE_ID`) instead of 1, as Windows only looks for
+    /// that one when loading the library. This happens automatically for every builder with the file type [`FileType::Dll`].
+    ///
+    /// [`ResourceBuilder::from_env`] already does all of this for packages that only build a `cdylib`,
+    /// so this is meant for packages that build both a binary and a library or for builders not created from the environment.
+    pub fn for_dll(self) -> Self {
+        self.add_crate_type(CrateType::Cdylib)
+            .set_file_type(FileType::Dll)
+    }
+
     pub fn set_file_type(mut self, file_type: FileType) -> Self {
         self.version.file_type = file_type;
         self
     }
 
-    pub fn add_file_flags(mut self, flags: impl IntoIterator<Item = FileFlag>) -> Self {
-        for flag in flags {
-            self.version.flags.insert(flag);
-        }
+    /// Sets the operating system the file was designed for. Defaults to [`FileOs::NtWindows32`].
+    pub fn set_file_os(mut self, file_os: FileOs) -> Self {
+        self.version.file_os = file_os;
         self
     }
 
-    pub fn add_string<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
-        self.version.strings.insert(key.into(), value.into());
+    /// Sets the function of the file, e.g. the `VFT2_DRV_*` kind of a driver or the `VFT2_FONT_*` kind of a font.
+    /// Should be 0 for all other file types.
+    pub fn set_file_subtype(mut self, subtype: u32) -> Self {
+        self.version.file_subtype = subtype;
         self
     }
 
-    pub fn add_manifest<S: Into<String>>(mut self, manifest: S) -> Self {
-        assert!(self.manifest.is_none(), "Manifest already set");
-        self.manifest = Some(manifest.into());
+    /// Sets the code page of the resources, which is written into every resource data entry and the version string table.
+    /// Only relevant for non-Unicode text resources, defaults to Unicode (1200).
+    pub fn set_codepage(mut self, codepage: u16) -> Self {
+        self.version.codepage = Some(codepage);
         self
     }
 
-    pub fn add_icon(mut self, id: u16, icon: Icon) -> Self {
-        assert!(!self.icon_groups.iter().any(|(i, _)| *i == id), "Duplicate icon id");
-        const ICON_BASE_ID: u16 = 128;
-        let icon_id = ICON_BASE_ID + self.icons.len() as u16;
-        self.icon_groups.push((
+    /// Sets the `MajorVersion` and `MinorVersion` fields of the top-level resource directory of COFF objects.
+    /// These are ignored by the linkers and usually left at 0, which is the default, but can be set to mirror the resources of another binary.
+    pub fn set_resource_version(mut self, major: u16, minor: u16) -> Self {
+        self.resource_version = (major, minor);
+        self
+    }
+
+    /// Sets the file date of the version info from a `FILETIME` value, in 100-nanosecond intervals since January 1, 1601 (UTC).
+    /// Defaults to 0, or to `SOURCE_DATE_EPOCH` when created with [`ResourceBuilder::from_env`].
+    pub fn set_version_timestamp(mut self, filetime: u64) -> Self {
+        self.version.file_date = filetime;
+        self
+    }
+
+    /// Sets which bits of the file flags are valid, defaults to `0x3f` (all flags defined by [`FileFlag`]).
+    pub fn set_file_flags_mask(mut self, mask: u32) -> Self {
+        self.version.flags_mask = Some(mask);
+        self
+    }
+
+    /// Turns the warnings about missing version strings in [`ResourceBuilder::finish`] into errors.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Makes [`ResourceBuilder::finish`] print a summary of what it embeds as cargo warnings, which is off by default.
+    ///
+    /// The summary lists the target and the type, id, language and data size of every resource before anything is written,
+    /// taken from the same resource directory that ends up in the object, followed by the path and size of every written object.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Overrides the linker toolchain, which is otherwise detected from `CARGO_CFG_TARGET_ENV`.
+    pub fn set_target_env(mut self, target_env: TargetEnv) -> Self {
+        self.target_env = Some(target_env);
+        self
+    }
+
+    /// Sets the name of the file written by [`ResourceBuilder::finish`] in `OUT_DIR`, which is `resources` by default.
+    ///
+    /// Without an extension, the one expected by the linker of the target environment is appended.
+    /// This allows a build script to link multiple resource objects, as each needs its own file.
+    ///
+    /// # Panics
+    /// Panics if the name is not a plain file name.
+    pub fn set_output_name(mut self, name: &str) -> Self {
+        assert!(Path::new(name).file_name().is_some_and(|n| n == name), "Invalid output name {name:?}");
+        self.output_name = Some(name.to_string());
+        self
+    }
+
+    /// Makes [`ResourceBuilder::finish`] write one object per group of resources instead of a single one,
+    /// so that a change to one group leaves the objects of the others untouched. See [`ResourceBuilder::compile_split`] for the groups.
+    ///
+    /// GNU `ld` merges the resource directories of all objects when linking. `link.exe` and `lld-link` in MSVC mode
+    /// only accept a single resource object, so this setting is ignored with a warning for MSVC targets.
+    pub fn split_objects(mut self, split: bool) -> Self {
+        self.split_objects = split;
+        self
+    }
+
+    /// Adds a crate type that the resources should be linked into. Defaults to [`CrateType::Bin`] if none is set.
+    ///
+    /// Adding only [`CrateType::Cdylib`] also changes the file type to [`FileType::Dll`].
+    /// Call [`ResourceBuilder::set_file_type`] afterward to override this.
+    pub fn add_crate_type(mut self, crate_type: CrateType) -> Self {
+        self.crate_types.insert(crate_type);
+        if crate_type == CrateType::Cdylib && !self.crate_types.contains(&CrateType::Bin) {
+            self.version.file_type = FileType::Dll;
+        }
+        self
+    }
+
+    pub fn add_file_flag(mut self, flag: FileFlag) -> Self {
+        self.version.flags.insert(flag);
+        self
+    }
+
+    /// Adds all flags of the set, which can also be given as an array, a `Vec` or a `BTreeSet` of flags.
+    /// Flags that require a version string are checked when the resources are compiled,
+    /// see [`FileFlags::validate`].
+    pub fn add_file_flags(mut self, flags: impl Into<FileFlags>) -> Self {
+        for flag in flags.into() {
+            self.version.flags.insert(flag);
+        }
+        self
+    }
+
+    /// Adds a string to the version info, e.g. `ProductName` or `FileDescription`.
+    ///
+    /// Keys and values may contain any Unicode characters, e.g. `©` in `LegalCopyright`.
+    /// Characters outside the Basic Multilingual Plane are stored as surrogate pairs and count as two characters.
+    ///
+    /// The version info stores its size in 16 bits, so all keys and values together (encoded as UTF-16) must stay below 64 KiB.
+    /// In practice this limits a single value to a bit more than 32,000 characters.
+    /// Exceeding the limit makes [`ResourceBuilder::try_finish`] fail with [`EmbedError::VersionStringTooLong`].
+    pub fn add_string<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.version.strings.insert(key.into(), value.into());
+        self
+    }
+
+    /// Removes a string from the version info, returning its value if it was set.
+    pub fn remove_string(&mut self, key: &str) -> Option<String> {
+        self.version.strings.remove(key)
+    }
+
+    /// Sets the application manifest.
+    ///
+    /// The manifest gets the id 1, or 2 if the file type is [`FileType::Dll`], see [`ResourceBuilder::for_dll`],
+    /// and is [language neutral](LanguageId::NEUTRAL).
+    ///
+    /// The manifest is checked for obvious XML mistakes like unbalanced tags when the resources are written,
+    /// making [`ResourceBuilder::try_finish`] fail with [`EmbedError::InvalidManifest`].
+    pub fn add_manifest<S: Into<String>>(self, manifest: S) -> Self {
+        self.add_manifest_lang(LanguageId::NEUTRAL, manifest)
+    }
+
+    /// Like [`ResourceBuilder::add_manifest`], but for a specific language.
+    pub fn add_manifest_lang<S: Into<String>>(mut self, language: LanguageId, manifest: S) -> Self {
+        assert!(self.manifest.is_none(), "Manifest already set");
+        self.assert_unused(ResourceType::Manifest, self.manifest_id(), language);
+        self.manifest = Some((language, manifest.into().into_bytes()));
+        self
+    }
+
+    /// Sets the application manifest from raw bytes, which are embedded as they are instead of as UTF-8.
+    ///
+    /// This is meant for manifests in a legacy encoding declared by their XML declaration.
+    /// A leading UTF-8 byte order mark is removed, as Windows refuses to load some manifests that start with one.
+    /// The manifest is still checked for obvious XML mistakes, like with [`ResourceBuilder::add_manifest`].
+    pub fn add_manifest_bytes(self, manifest: Vec<u8>) -> Self {
+        self.add_manifest_bytes_lang(LanguageId::NEUTRAL, manifest)
+    }
+
+    /// Like [`ResourceBuilder::add_manifest_bytes`], but for a specific language.
+    pub fn add_manifest_bytes_lang(mut self, language: LanguageId, mut manifest: Vec<u8>) -> Self {
+        assert!(self.manifest.is_none(), "Manifest already set");
+        self.assert_unused(ResourceType::Manifest, self.manifest_id(), language);
+        const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+        if manifest.starts_with(UTF8_BOM) {
+            manifest.drain(..UTF8_BOM.len());
+        }
+        self.manifest = Some((language, manifest));
+        self.manifest_raw = true;
+        self
+    }
+
+    /// Sets a manifest generated by a [`ManifestBuilder`].
+    pub fn add_generated_manifest(self, manifest: ManifestBuilder) -> Self {
+        self.add_manifest(manifest.build())
+    }
+
+    /// Like [`ResourceBuilder::add_manifest`], but embeds the data as is without checking that it is well-formed XML.
+    pub fn add_manifest_unchecked<S: Into<String>>(mut self, manifest: S) -> Self {
+        self = self.add_manifest(manifest);
+        self.manifest_unchecked = true;
+        self
+    }
+
+    /// Reads the manifest from a file. The contents are embedded as they are, see [`ResourceBuilder::add_manifest_bytes`].
+    /// When running in a build script, cargo is instructed to rerun it if the file changes.
+    pub fn add_manifest_path<P: AsRef<Path>>(self, path: P) -> Self {
+        let path = path.as_ref();
+        let manifest = std::fs::read(path).unwrap_or_else(|err| panic!("Failed to read manifest {}: {err}", path.display()));
+        rerun_if_changed(path);
+        self.add_manifest_bytes(manifest)
+    }
+
+    /// Adds a [language neutral](LanguageId::NEUTRAL) manifest with a custom id, besides the application manifest.
+    ///
+    /// Windows only loads the manifests with the ids 1 to 16 on its own. Manifests with other ids are meant for activation contexts
+    /// that are created explicitly with `CreateActCtx`, using `ACTCTX_FLAG_RESOURCE_NAME_VALID` and the id as the resource name.
+    /// Every manifest is checked for obvious XML mistakes, like with [`ResourceBuilder::add_manifest`].
+    ///
+    /// # Panics
+    /// Panics if the id is 0 or already taken by another manifest, including the application manifest.
+    pub fn add_activation_manifest<S: Into<String>>(mut self, id: u16, manifest: S) -> Self {
+        assert!(id != 0, "Resource id 0 is reserved");
+        self.assert_unused(ResourceType::Manifest, id, LanguageId::NEUTRAL);
+        insert_sorted(&mut self.activation_manifests, (id, manifest.into().into_bytes()), |(id, _)| *id);
+        self
+    }
+
+    /// Removes the application manifest, so that a different one can be set.
+    /// Manifests added with [`ResourceBuilder::add_activation_manifest`] are kept.
+    pub fn clear_manifest(&mut self) {
+        self.manifest = None;
+        self.manifest_unchecked = false;
+        self.manifest_raw = false;
+    }
+
+    /// Loads a PNG icon from a file. See [`Icon::from_png_bytes`] for the supported formats.
+    /// When running in a build script, cargo is instructed to rerun it if the file changes.
+    pub fn add_icon_path<P: AsRef<Path>>(self, id: u16, path: P) -> Self {
+        let path = path.as_ref();
+        let icon = Icon::from_png_file(path).unwrap_or_else(|err| panic!("Failed to read icon {}: {err}", path.display()));
+        rerun_if_changed(path);
+        self.add_icon(id, icon)
+    }
+
+    /// Loads all images of an `.ico` file into one icon group. See [`Icon::from_ico_bytes`] for the supported files.
+    /// When running in a build script, cargo is instructed to rerun it if the file changes.
+    pub fn add_icon_ico_path<P: AsRef<Path>>(self, id: u16, path: P) -> Self {
+        let path = path.as_ref();
+        let icons = Icon::from_ico_file(path).unwrap_or_else(|err| panic!("Failed to read icon {}: {err}", path.display()));
+        rerun_if_changed(path);
+        self.add_icon_group(id, icons)
+    }
+
+    /// Adds an icon with a single image. Like all icons added without an explicit language, it is [language neutral](LanguageId::NEUTRAL).
+    pub fn add_icon(self, id: u16, icon: Icon) -> Self {
+        self.add_icon_group(id, [icon])
+    }
+
+    /// Like [`ResourceBuilder::add_icon`], but for a specific language.
+    pub fn add_icon_lang(self, id: u16, language: LanguageId, icon: Icon) -> Self {
+        self.add_icon_group_lang(id, language, [icon])
+    }
+
+    /// Adds a single icon that consists of multiple images, e.g. different resolutions of the same icon.
+    /// Windows picks the best matching image when displaying the icon.
+    ///
+    /// Every image is stored as its own `RT_ICON` resource, which gets the lowest id from 128 upwards that is not
+    /// taken yet. Images that were already added to the same language reuse their id, so the ids only depend on
+    /// the order of the calls. Use [`ResourceBuilder::add_icon_group_with_ids`] to choose them explicitly,
+    /// or [`ResourceBuilder::set_icon_id_base`] to move the range.
+    ///
+    /// The group refers to its images by these ids, so they share one namespace with all other `RT_ICON` resources,
+    /// including imported ones. Ids that are already taken by those are skipped.
+    pub fn add_icon_group(self, id: u16, icons: impl IntoIterator<Item = Icon>) -> Self {
+        self.add_icon_group_lang(id, LanguageId::NEUTRAL, icons)
+    }
+
+    /// Adds an icon with images in all of the [standard sizes](Icon::STANDARD_SIZES), downscaled from `source` with a Lanczos filter.
+    ///
+    /// Sizes larger than the source are skipped. Like in the `.ico` files Windows ships, the 256px image is stored
+    /// as PNG, while the smaller ones are stored as uncompressed bitmaps.
+    #[cfg(feature = "image")]
+    pub fn add_icon_auto(self, id: u16, source: &image::DynamicImage) -> Self {
+        self.add_icon_auto_sizes(id, source, &Icon::STANDARD_SIZES)
+    }
+
+    /// Like [`ResourceBuilder::add_icon_auto`], but with a custom list of sizes.
+    /// Sizes of 256 and up are stored as PNG, with sizes above [`Icon::MAX_SIZE`] being clamped to it.
+    #[cfg(feature = "image")]
+    pub fn add_icon_auto_sizes(self, id: u16, source: &image::DynamicImage, sizes: &[u32]) -> Self {
+        let icons = sizes
+            .iter()
+            .filter(|&&size| size <= source.width().max(source.height()))
+            .map(|&size| {
+                let img = source.resize(size, size, image::imageops::FilterType::Lanczos3);
+                match size >= Icon::MAX_SIZE {
+                    true => Icon::from_dynamic_image(img),
+                    false => Icon::from_dynamic_image_dib(img)
+                }
+            })
+            .collect::<Vec<_>>();
+        assert!(!icons.is_empty(), "The source image is smaller than all of the requested icon sizes");
+        self.add_icon_group(id, icons)
+    }
+
+    /// Like [`ResourceBuilder::add_icon_group`], but for a specific language.
+    pub fn add_icon_group_lang(self, id: u16, language: LanguageId, icons: impl IntoIterator<Item = Icon>) -> Self {
+        self.push_icon_group(id, language, icons.into_iter().map(|icon| (None, icon)))
+    }
+
+    /// Like [`ResourceBuilder::add_icon_group`], but with fixed ids for the `RT_ICON` resources of the images,
+    /// for applications that load individual images by id.
+    ///
+    /// An image id can only be used again for the same image.
+    pub fn add_icon_group_with_ids(self, id: u16, icons: impl IntoIterator<Item = (u16, Icon)>) -> Self {
+        self.push_icon_group(
             id,
-            [IconGroupEntry {
+            LanguageId::NEUTRAL,
+            icons
+                .into_iter()
+                .map(|(image_id, icon)| (Some(image_id), icon))
+        )
+    }
+
+    /// Sets the lowest id for the `RT_ICON` resources of images added without an explicit id, defaults to 128.
+    ///
+    /// This only affects icons added afterward. It is useful to keep the images out of an id range
+    /// that is reserved for `RT_ICON` resources added later, e.g. by importing another resource file.
+    pub fn set_icon_id_base(mut self, base: u16) -> Self {
+        self.icon_id_base = Some(base);
+        self
+    }
+
+    /// Adds a DIB copy in front of every PNG image smaller than [`Icon::MAX_SIZE`] of the icons added afterward.
+    ///
+    /// Windows only reads PNG images in icons since Vista, earlier versions draw nothing for them:
+    ///
+    /// | Windows         | DIB images | PNG images |
+    /// |-----------------|------------|------------|
+    /// | XP and earlier  | yes        | no         |
+    /// | Vista and later | yes        | yes        |
+    ///
+    /// Both images have the same size and bit count, so every version picks the DIB, which comes first.
+    /// The PNG stays in the group for tools that extract the original images. 256px images are left alone,
+    /// as Windows versions without PNG support can't show that size anyway.
+    ///
+    /// # Panics
+    /// Adding an icon afterward panics if one of its PNG images can't be decoded, see [`IconError::CorruptPng`].
+    #[cfg(feature = "image")]
+    pub fn require_bmp_fallback(mut self, fallback: bool) -> Self {
+        self.bmp_fallback = fallback;
+        self
+    }
+
+    /// Adds another id for an existing icon group, e.g. to use the same icon for the window and the tray.
+    /// Both ids refer to the same images, so nothing is stored twice.
+    ///
+    /// The alias is added in every language the existing icon has.
+    ///
+    /// # Panics
+    /// Panics if there is no icon group with `existing_id` or if `id` is already taken.
+    pub fn add_icon_alias(mut self, id: u16, existing_id: u16) -> Self {
+        let groups: Vec<_> = self
+            .icon_groups
+            .iter()
+            .filter(|(i, _, _)| *i == existing_id)
+            .map(|(_, language, entries)| (*language, entries.clone()))
+            .collect();
+        assert!(!groups.is_empty(), "Can't alias icon {existing_id}, as there is no icon with that id");
+        for (language, entries) in groups {
+            self.assert_unused(ResourceType::IconGroup, id, language);
+            insert_sorted(&mut self.icon_groups, (id, language, entries), |(id, language, _)| (*id, *language));
+        }
+        self
+    }
+
+    /// Removes the icon with the given id in all languages, returning whether there was one.
+    ///
+    /// Its images are removed as well, unless they are still used by another icon, like an alias.
+    pub fn remove_icon(&mut self, id: u16) -> bool {
+        let count = self.icon_groups.len();
+        self.icon_groups.retain(|(i, _, _)| *i != id);
+        let groups = &self.icon_groups;
+        self.icons.retain(|(image_id, language, _)| {
+            groups
+                .iter()
+                .any(|(_, l, entries)| l == language && entries.iter().any(|e| e.icon_id == *image_id))
+        });
+        self.icon_groups.len() != count
+    }
+
+    /// Removes all icons and their images, e.g. to add a regenerated set to a shared builder.
+    ///
+    /// Image ids are always the lowest free ones, so adding the same icons again yields the same ids as on a fresh builder.
+    /// `RT_ICON` resources from imported resource files are kept.
+    pub fn clear_icons(&mut self) {
+        self.icon_groups.clear();
+        self.icons.clear();
+    }
+
+    fn push_icon_group(mut self, id: u16, language: LanguageId, icons: impl IntoIterator<Item = (Option<u16>, Icon)>) -> Self {
+        self.assert_unused(ResourceType::IconGroup, id, language);
+        const ICON_BASE_ID: u16 = 128;
+        let base = self.icon_id_base.unwrap_or(ICON_BASE_ID);
+        #[cfg(feature = "image")]
+        let icons = {
+            let fallback = self.bmp_fallback;
+            icons
+                .into_iter()
+                .flat_map(|(image_id, icon)| {
+                    let dib = (fallback && icon.is_png() && icon.width < Icon::MAX_SIZE && icon.height < Icon::MAX_SIZE).then(|| {
+                        let dib = icon
+                            .png_to_dib()
+                            .unwrap_or_else(|err| panic!("Failed to create the BMP fallback of an icon: {err}"));
+                        (None, dib)
+                    });
+                    dib.into_iter().chain([(image_id, icon)])
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut entries = Vec::new();
+        for (image_id, icon) in icons {
+            let existing = match image_id {
+                Some(image_id) => self
+                    .icons
+                    .iter()
+                    .find(|(i, l, _)| *i == image_id && *l == language)
+                    .map(|(i, _, other)| {
+                        assert!(other.same_content(&icon), "Duplicate icon image id {image_id}");
+                        *i
+                    })
+                    .or_else(|| {
+                        self.assert_unused(ResourceType::Icon, image_id, language);
+                        None
+                    }),
+                // Groups of the same language can share identical images
+                None => self
+                    .icons
+                    .iter()
+                    .find(|(_, l, i)| *l == language && i.same_content(&icon))
+                    .map(|(id, _, _)| *id)
+            };
+            let icon_id = existing.or(image_id).unwrap_or_else(|| {
+                // the loader looks up the images by id in any language, so ids taken in other languages are skipped as well
+                (base..=u16::MAX)
+                    .find(|i| {
+                        !self
+                            .resource_keys()
+                            .any(|(ty, id, _)| ty == ResourceType::Icon && id == *i)
+                    })
+                    .expect("Too many icons")
+            });
+            entries.push(IconGroupEntry {
                 icon_id,
-                icon_size: icon.0.len()
-            }]
-        ));
-        self.icons.push((icon_id, icon));
+                icon_size: icon.data.len(),
+                width: icon.width,
+                height: icon.height,
+                bit_count: icon.bit_count
+            });
+            if existing.is_none() {
+                insert_sorted(&mut self.icons, (icon_id, language, icon), |(id, language, _)| (*id, *language));
+            }
+        }
+        assert!(!entries.is_empty(), "Icon group must contain at least one icon");
+        insert_sorted(&mut self.icon_groups, (id, language, entries), |(id, language, _)| (*id, *language));
+        self
+    }
+
+    /// Adds the version info for another language, e.g. to show localized product names in Explorer.
+    /// Using [`LanguageId::LANG_US`] replaces the version info configured by the other setters.
+    pub fn add_version_for_language(mut self, language: LanguageId, version: VersionInfo) -> Self {
+        if language == LanguageId::LANG_US {
+            self.version = version;
+        } else {
+            if !self.localized_versions.contains_key(&language) {
+                self.assert_unused(ResourceType::Version, 1, language);
+            }
+            self.localized_versions.insert(language, version);
+        }
+        self
+    }
+
+    /// Adds a dialog box template that can be loaded with `CreateDialog`/`DialogBox`.
+    pub fn add_dialog(mut self, id: u16, dialog: DialogTemplate) -> Self {
+        self.assert_unused(ResourceType::Dialog, id, LanguageId::LANG_US);
+        insert_sorted(&mut self.dialogs, (id, dialog), |(id, _)| *id);
+        self
+    }
+
+    /// Adds an accelerator table that can be loaded with `LoadAccelerators`.
+    pub fn add_accelerators(mut self, id: u16, entries: Vec<Accelerator>) -> Self {
+        self.assert_unused(ResourceType::Accelerator, id, LanguageId::LANG_US);
+        assert!(!entries.is_empty(), "Accelerator table must contain at least one entry");
+        insert_sorted(&mut self.accelerators, (id, entries), |(id, _)| *id);
+        self
+    }
+
+    /// Adds an HTML (or any other web content) resource that can be loaded using a `res://` url.
+    ///
+    /// The data is embedded verbatim, so the caller is responsible for using an encoding that the consumer understands.
+    pub fn add_html(self, id: u16, data: Vec<u8>) -> Self {
+        self.add_raw_resource(ResourceType::Html, id, data)
+    }
+
+    /// Adds an animated cursor from an `.ani` file that can be loaded with `LoadCursor`.
+    ///
+    /// # Panics
+    /// Panics if the data is not a RIFF file of type `ACON`.
+    pub fn add_ani_cursor(self, id: u16, data: Vec<u8>) -> Self {
+        assert!(
+            data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"ACON",
+            "Invalid animated cursor: the data must be an .ani file starting with a RIFF ACON header"
+        );
+        let riff_size = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        assert!(
+            riff_size + 8 <= data.len(),
+            "Invalid animated cursor: the RIFF header claims more data than there is"
+        );
+        self.add_raw_resource(ResourceType::AniCursor, id, data)
+    }
+
+    /// Adds a file that is identified by its name instead of an id, e.g. the assets of an embedded web view.
+    /// The data is embedded verbatim in English (US), like with [`ResourceBuilder::add_raw_resource`].
+    ///
+    /// Windows looks up resource names case-insensitively by converting them to uppercase, so the name is stored in uppercase.
+    /// At runtime the file is found with the name in any case, e.g. `FindResourceW(module, w!("index.html"), RT_HTML)`
+    /// followed by `LoadResource`, `LockResource` and `SizeofResource`. [HTML](ResourceType::Html) files can also be opened
+    /// with a `res://app.exe/index.html` URL.
+    ///
+    /// # Panics
+    /// Panics if the name is empty or starts with `#`, which Windows treats as a numeric id,
+    /// if the type is [`ResourceType::None`], or if a file with the same type and name was already added.
+    pub fn add_file(mut self, name: &str, ty: ResourceType, data: Vec<u8>) -> Self {
+        assert!(!name.is_empty() && !name.starts_with('#'), "Invalid resource name {name:?}");
+        assert!(ty != ResourceType::None, "Resource type 0 is reserved");
+        let name = name.to_uppercase();
+        assert!(
+            !self
+                .named_resources
+                .iter()
+                .any(|(t, n, _)| *t == ty && *n == name),
+            "Duplicate resource: type {ty}, name {name:?}"
+        );
+        insert_sorted(&mut self.named_resources, (ty, name, data), |(ty, name, _)| (*ty, name.clone()));
         self
     }
 
+    /// Adds a compiled type library (`.tlb`) for a COM server, as `rc.exe` does for a `TYPELIB` statement.
+    ///
+    /// The resource is stored under the named type `TYPELIB` that `LoadTypeLib` looks for.
+    /// Without an explicit index in the path, `LoadTypeLib` loads the type library with id 1,
+    /// so that is the id to use for a server with a single type library.
+    pub fn add_typelib(self, id: u16, tlb: Vec<u8>) -> Self {
+        self.add_raw_resource(ResourceType::TypeLib, id, tlb)
+    }
+
+    /// Adds a resource of any type with full control over its header, as an escape hatch for types this crate doesn't support.
+    /// See [`RawResource`] for the meaning of the fields.
+    ///
+    /// # Panics
+    /// Panics if the type id is 0 or the resource is already taken.
+    pub fn add_raw_resource_full(mut self, resource: RawResource) -> Self {
+        let ty = ResourceType::from_id(resource.type_id);
+        assert!(ty != ResourceType::None, "Resource type 0 is reserved");
+        self.assert_unused(ty, resource.id, resource.language);
+        let resource = ImportedResource {
+            ty,
+            id: resource.id,
+            language: resource.language,
+            flags: resource.memory_flags,
+            data_version: resource.data_version,
+            version: resource.version,
+            characteristics: resource.characteristics,
+            data: resource.data
+        };
+        insert_sorted(&mut self.imported, resource, |r| (r.ty, r.id, r.language));
+        self
+    }
+
+    /// Adds a resource of any type with the data embedded verbatim, e.g. a [`ResourceType::Custom`] type of an application.
+    /// Like most resources, it is added in English (US). Use [`ResourceBuilder::add_raw_resource_full`] for other languages.
+    ///
+    /// # Panics
+    /// Panics if the type is [`ResourceType::None`] or the resource is already taken.
+    pub fn add_raw_resource(mut self, ty: ResourceType, id: u16, data: Vec<u8>) -> Self {
+        assert!(ty != ResourceType::None, "Resource type 0 is reserved");
+        self.assert_unused(ty, id, LanguageId::LANG_US);
+        insert_sorted(&mut self.raw_resources, (ty, id, data), |(ty, id, _)| (*ty, *id));
+        self
+    }
+
+    /// The ids of all icons added with [`ResourceBuilder::add_icon`] or [`ResourceBuilder::add_icon_group`], in any language.
+    pub fn icon_ids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.icon_groups
+            .iter()
+            .map(|(id, _, _)| *id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+    }
+
+    /// Whether a manifest was set with [`ResourceBuilder::add_manifest`] or one of its variants.
+    pub fn has_manifest(&self) -> bool {
+        self.manifest.is_some()
+    }
+
+    /// The strings of the default version info, by key.
+    pub fn version_strings(&self) -> &BTreeMap<String, String> {
+        &self.version.strings
+    }
+
+    /// The file flags of the default version info.
+    pub fn file_flags(&self) -> &BTreeSet<FileFlag> {
+        &self.version.flags
+    }
+
+    /// Compiles the resources into the RES format used by `rc.exe`, which can be consumed by tools like `cvtres.exe`.
+    ///
+    /// # Panics
+    /// Panics if [`ResourceBuilder::try_build_res`] fails.
+    pub fn build_res(self) -> ResourceFile {
+        self.try_build_res()
+            .unwrap_or_else(|err| panic!("Failed to compile resources: {err}"))
+    }
+
+    /// Like [`ResourceBuilder::build_res`], but returns an error instead of panicking.
+    pub fn try_build_res(self) -> Result<ResourceFile, EmbedError> {
+        self.validate()?;
+        Ok(self.compile_to_res())
+    }
+
+    /// Merges all resources of an existing RES file, e.g. one compiled by `rc.exe`, into this builder.
+    ///
+    /// The language of each resource is preserved.
+    /// Resources that collide with a resource of the builder (same type, id and language) are rejected.
+    ///
+    /// # Panics
+    /// Panics if the file can't be imported, see [`ResourceBuilder::try_add_res_file`].
+    pub fn add_res_file(self, bytes: Vec<u8>) -> Self {
+        self.try_add_res_file(bytes)
+            .unwrap_or_else(|err| panic!("Failed to add RES file: {err}"))
+    }
+
+    /// Like [`ResourceBuilder::add_res_file`], but returns an error if the file can't be imported.
+    ///
+    /// Resources identified by a name end up next to the ones of [`ResourceBuilder::add_file`], so they have to be in
+    /// English (US) and lose the other fields of their header. Types identified by a name other than `TYPELIB` are not supported.
+    pub fn try_add_res_file(mut self, bytes: Vec<u8>) -> Result<Self, EmbedError> {
+        use parse::ResourceName;
+        let entries = parse::parse_res(&bytes).map_err(EmbedError::InvalidResFile)?;
+        for entry in entries {
+            let unsupported = || EmbedError::UnsupportedResource {
+                ty: entry.ty.to_string(),
+                name: entry.name.to_string()
+            };
+            let ty = match &entry.ty {
+                ResourceName::Id(ty) => ResourceType::from_id(u16::try_from(*ty).map_err(|_| unsupported())?),
+                ResourceName::Name(name) if *name == ResourceType::TypeLib.name().unwrap() => ResourceType::TypeLib,
+                ResourceName::Name(_) => return Err(unsupported())
+            };
+            if ty == ResourceType::None {
+                continue;
+            }
+            let language = LanguageId(entry.language);
+            match entry.name {
+                ResourceName::Id(id) => {
+                    let id = u16::try_from(id).map_err(|_| unsupported())?;
+                    if self.has_resource(ty, id, language) {
+                        return Err(EmbedError::DuplicateResource {
+                            ty: ty.to_string(),
+                            id,
+                            language
+                        });
+                    }
+                    let resource = ImportedResource {
+                        ty,
+                        id,
+                        language,
+                        flags: entry.memory_flags,
+                        data_version: entry.data_version,
+                        version: entry.version,
+                        characteristics: entry.characteristics,
+                        data: entry.data
+                    };
+                    insert_sorted(&mut self.imported, resource, |r| (r.ty, r.id, r.language));
+                }
+                ResourceName::Name(ref name) => {
+                    if language != LanguageId::LANG_US || name.is_empty() || name.starts_with('#') {
+                        return Err(unsupported());
+                    }
+                    let name = name.to_uppercase();
+                    if self
+                        .named_resources
+                        .iter()
+                        .any(|(t, n, _)| *t == ty && *n == name)
+                    {
+                        return Err(EmbedError::DuplicateNamedResource { ty: ty.to_string(), name });
+                    }
+                    insert_sorted(&mut self.named_resources, (ty, name, entry.data), |(ty, name, _)| (*ty, name.clone()));
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// The type, id and language of every resource the builder is going to write.
+    /// This is the single source of truth for collisions between the different kinds of resources.
+    fn resource_keys(&self) -> impl Iterator<Item = (ResourceType, u16, LanguageId)> + '_ {
+        let us = LanguageId::LANG_US;
+        std::iter::once((ResourceType::Version, 1, us))
+            .filter(|_| !self.version.is_empty())
+            .chain(
+                self.localized_versions
+                    .keys()
+                    .map(|l| (ResourceType::Version, 1, *l))
+            )
+            .chain(
+                self.icons
+                    .iter()
+                    .map(|(id, l, _)| (ResourceType::Icon, *id, *l))
+            )
+            .chain(
+                self.icon_groups
+                    .iter()
+                    .map(|(id, l, _)| (ResourceType::IconGroup, *id, *l))
+            )
+            .chain(
+                self.dialogs
+                    .iter()
+                    .map(move |(id, _)| (ResourceType::Dialog, *id, us))
+            )
+            .chain(
+                self.accelerators
+                    .iter()
+                    .map(move |(id, _)| (ResourceType::Accelerator, *id, us))
+            )
+            .chain(
+                self.raw_resources
+                    .iter()
+                    .map(move |(ty, id, _)| (*ty, *id, us))
+            )
+            .chain(self.imported.iter().map(|r| (r.ty, r.id, r.language)))
+            .chain(
+                self.manifest
+                    .iter()
+                    .map(|(l, _)| (ResourceType::Manifest, self.manifest_id(), *l))
+            )
+            .chain(
+                self.activation_manifests
+                    .iter()
+                    .map(|(id, _)| (ResourceType::Manifest, *id, LanguageId::NEUTRAL))
+            )
+    }
+
+    /// The id of the manifest, which depends on whether Windows loads it for a process or for a library.
+    pub(crate) fn manifest_id(&self) -> u16 {
+        const CREATEPROCESS_MANIFEST_RESOURCE_ID: u16 = 1;
+        const ISOLATIONAWARE_MANIFEST_RESOURCE_ID: u16 = 2;
+        match self.version.file_type {
+            FileType::Dll => ISOLATIONAWARE_MANIFEST_RESOURCE_ID,
+            _ => CREATEPROCESS_MANIFEST_RESOURCE_ID
+        }
+    }
+
+    /// Whether there are no resources to embed. [`ResourceBuilder::finish`] skips such a builder with a warning.
+    pub fn is_empty(&self) -> bool {
+        self.resource_keys().next().is_none() && self.named_resources.is_empty()
+    }
+
+    fn has_resource(&self, ty: ResourceType, id: u16, language: LanguageId) -> bool {
+        self.resource_keys().any(|key| key == (ty, id, language))
+    }
+
+    /// Panics if a resource with the same type, id and language was already added.
+    /// Different types can use the same ids.
+    fn assert_unused(&self, ty: ResourceType, id: u16, language: LanguageId) {
+        assert!(
+            !self.has_resource(ty, id, language),
+            "Duplicate resource: type {ty}, id {id}, language {:#06x}",
+            language.0
+        );
+    }
+
+    /// Roughly estimates the size of all resources including their headers, so the output buffers don't have to grow as often.
+    fn estimated_size(&self) -> usize {
+        const HEADER: usize = 32;
+        let version = |v: &VersionInfo| {
+            256 + v
+                .strings
+                .iter()
+                .map(|(k, v)| 8 + 2 * (k.len() + v.len()))
+                .sum::<usize>()
+        };
+        let versions: usize = std::iter::once(&self.version)
+            .chain(self.localized_versions.values())
+            .map(version)
+            .sum();
+        let icons: usize = self
+            .icons
+            .iter()
+            .map(|(_, _, icon)| HEADER + icon.data.len())
+            .sum();
+        let icon_groups: usize = self
+            .icon_groups
+            .iter()
+            .map(|(_, _, entries)| HEADER + 6 + 14 * entries.len())
+            .sum();
+        let raw: usize = self
+            .raw_resources
+            .iter()
+            .map(|(_, _, data)| HEADER + data.len())
+            .chain(self.imported.iter().map(|r| HEADER + r.data.len()))
+            .chain(
+                self.named_resources
+                    .iter()
+                    .map(|(_, name, data)| HEADER + 2 * name.len() + data.len())
+            )
+            .sum();
+        let manifest = self
+            .manifest
+            .as_ref()
+            .map_or(0, |(_, manifest)| HEADER + manifest.len())
+            + self
+                .activation_manifests
+                .iter()
+                .map(|(_, manifest)| HEADER + manifest.len())
+                .sum::<usize>();
+        // dialogs and accelerators are small enough to not matter
+        versions + icons + icon_groups + raw + manifest + HEADER * (1 + self.dialogs.len() + self.accelerators.len())
+    }
+
     #[doc(hidden)]
     pub fn compile_to_res(&self) -> ResourceFile {
-        let mut res = ResWriter::default();
+        let mut res = ResWriter::with_capacity(self.estimated_size());
 
         res.write_resource(ResourceType::None, 0, &()); // Files seem to start with an empty resource
-        res.write_resource(ResourceType::Version, 1, &self.version);
-        for (id, icon) in &self.icons {
-            res.write_resource(ResourceType::Icon, *id, icon);
+        let translations = self.version_translations();
+        if !self.version.is_empty() {
+            res.write_resource(
+                ResourceType::Version,
+                1,
+                &VersionResource(LanguageId::LANG_US, &self.version, &translations)
+            );
+        }
+        for (language, version) in &self.localized_versions {
+            res.write_resource_with_language(ResourceType::Version, 1, *language, &VersionResource(*language, version, &translations));
+        }
+        for (id, language, icon) in &self.icons {
+            res.write_resource_with_language(ResourceType::Icon, *id, *language, icon);
+        }
+        for (id, language, entries) in &self.icon_groups {
+            res.write_resource_with_language(ResourceType::IconGroup, *id, *language, entries.as_slice());
+        }
+        for (id, dialog) in &self.dialogs {
+            res.write_resource(ResourceType::Dialog, *id, dialog);
         }
-        for (id, entries) in &self.icon_groups {
-            res.write_resource(ResourceType::IconGroup, *id, entries.as_slice());
+        for (id, entries) in &self.accelerators {
+            res.write_resource(ResourceType::Accelerator, *id, entries.as_slice());
         }
-        if let Some(manifest) = &self.manifest {
-            res.write_resource(ResourceType::Manifest, 1, manifest.as_bytes());
+        for (ty, id, data) in &self.raw_resources {
+            res.write_resource(*ty, *id, data);
+        }
+        for r in &self.imported {
+            let header = ResHeader {
+                data_version: r.data_version,
+                flags: r.flags,
+                language: r.language,
+                version: r.version,
+                characteristics: r.characteristics
+            };
+            res.write_resource_with(r.ty, r.id, header, &r.data);
+        }
+        for (ty, name, data) in &self.named_resources {
+            res.write_named_resource(*ty, name, LanguageId::LANG_US, data);
+        }
+        if let Some((language, manifest)) = &self.manifest {
+            res.write_resource_with_language(ResourceType::Manifest, self.manifest_id(), *language, manifest.as_slice());
+        }
+        for (id, manifest) in &self.activation_manifests {
+            res.write_resource_with_language(ResourceType::Manifest, *id, LanguageId::NEUTRAL, manifest.as_slice());
         }
         ResourceFile {
             data: res.finish(),
@@ -262,55 +2137,690 @@ impl ResourceBuilder {
 
     #[doc(hidden)]
     pub fn compile_to_coff(&self, target: TargetType) -> ResourceFile {
-        let mut writer = CoffWriter::new(target);
+        self.try_compile_to_coff(target)
+            .unwrap_or_else(|err| panic!("Failed to compile resources: {err}"))
+    }
+
+    fn try_compile_to_coff(&self, target: TargetType) -> Result<ResourceFile, EmbedError> {
+        let mut writer = CoffWriter::with_capacity(target, self.estimated_size());
+        self.add_to_coff(&mut writer, |_| true);
+        Ok(ResourceFile {
+            data: writer.finish()?,
+            kind: ResourceFileKind::Coff
+        })
+    }
+
+    /// Adds the resources whose type is accepted by `filter` to the COFF writer.
+    fn add_to_coff(&self, writer: &mut CoffWriter, filter: impl Fn(ResourceType) -> bool) {
+        if let Some(codepage) = self.version.codepage {
+            writer.set_codepage(codepage.into());
+        }
+        writer.set_resource_version(self.resource_version.0, self.resource_version.1);
 
-        writer.add_resource(ResourceType::Version, 1, &self.version);
-        for (id, icon) in &self.icons {
-            writer.add_resource(ResourceType::Icon, *id as u32, icon);
+        if filter(ResourceType::Version) {
+            let translations = self.version_translations();
+            if !self.version.is_empty() {
+                writer.add_resource(
+                    ResourceType::Version,
+                    1,
+                    &VersionResource(LanguageId::LANG_US, &self.version, &translations)
+                );
+            }
+            for (language, version) in &self.localized_versions {
+                writer.add_resource_with_language(ResourceType::Version, 1, *language, &VersionResource(*language, version, &translations));
+            }
         }
-        for (id, entries) in &self.icon_groups {
-            writer.add_resource(ResourceType::IconGroup, *id as u32, entries.as_slice());
+        if filter(ResourceType::Icon) {
+            for (id, language, icon) in &self.icons {
+                writer.add_resource_with_language(ResourceType::Icon, *id as u32, *language, icon);
+            }
         }
-        if let Some(manifest) = &self.manifest {
-            writer.add_resource(ResourceType::Manifest, 1, manifest.as_bytes());
+        if filter(ResourceType::IconGroup) {
+            for (id, language, entries) in &self.icon_groups {
+                writer.add_resource_with_language(ResourceType::IconGroup, *id as u32, *language, entries.as_slice());
+            }
         }
+        if filter(ResourceType::Dialog) {
+            for (id, dialog) in &self.dialogs {
+                writer.add_resource(ResourceType::Dialog, *id as u32, dialog);
+            }
+        }
+        if filter(ResourceType::Accelerator) {
+            for (id, entries) in &self.accelerators {
+                writer.add_resource(ResourceType::Accelerator, *id as u32, entries.as_slice());
+            }
+        }
+        for (ty, id, data) in self.raw_resources.iter().filter(|(ty, _, _)| filter(*ty)) {
+            writer.add_resource(*ty, *id as u32, data);
+        }
+        for r in self.imported.iter().filter(|r| filter(r.ty)) {
+            writer.add_resource_with_language(r.ty, r.id as u32, r.language, &r.data);
+        }
+        for (ty, name, data) in self.named_resources.iter().filter(|(ty, _, _)| filter(*ty)) {
+            writer.add_named_resource(*ty, name, LanguageId::LANG_US, data);
+        }
+        if let Some((language, manifest)) = self
+            .manifest
+            .as_ref()
+            .filter(|_| filter(ResourceType::Manifest))
+        {
+            writer.add_resource_with_language(ResourceType::Manifest, self.manifest_id() as u32, *language, manifest.as_slice());
+        }
+        if filter(ResourceType::Manifest) {
+            for (id, manifest) in &self.activation_manifests {
+                writer.add_resource_with_language(ResourceType::Manifest, *id as u32, LanguageId::NEUTRAL, manifest.as_slice());
+            }
+        }
+    }
 
-        ResourceFile {
-            data: writer.finish(),
-            kind: ResourceFileKind::Coff
+    /// Compiles the resources into several COFF objects for `target`, each holding one group of resources:
+    ///
+    /// - `version` for the version info
+    /// - `icons` for the icons and their images
+    /// - `manifest` for the manifests
+    /// - `other` for everything else, including raw resources and dialogs
+    ///
+    /// Groups without resources are left out. Resources are grouped by their type, so imported and raw resources
+    /// end up next to the resources of the same type. Only GNU `ld` can link more than one of these objects,
+    /// see [`ResourceBuilder::split_objects`]. It only merges resources from objects that keep them in a single
+    /// `.rsrc` section like the ones produced by `windres`, so that is the layout used here.
+    ///
+    /// # Panics
+    /// Panics if [`ResourceBuilder::try_compile_split`] fails.
+    pub fn compile_split(&self, target: TargetType) -> Vec<(&'static str, Vec<u8>)> {
+        self.try_compile_split(target)
+            .unwrap_or_else(|err| panic!("Failed to compile resources: {err}"))
+    }
+
+    /// Like [`ResourceBuilder::compile_split`], but returns an error instead of panicking.
+    pub fn try_compile_split(&self, target: TargetType) -> Result<Vec<(&'static str, Vec<u8>)>, EmbedError> {
+        self.validate()?;
+        let mut objects = Vec::new();
+        for group in ["version", "icons", "manifest", "other"] {
+            let mut writer = CoffWriter::with_capacity(target, 0).with_single_section();
+            self.add_to_coff(&mut writer, |ty| object_group(ty) == group);
+            if !writer.is_empty() {
+                objects.push((group, writer.finish()?));
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Renders the resources as an `rc.exe` resource script.
+    ///
+    /// This is a diagnostic aid to see what the builder is going to produce. The script is readable and roughly correct,
+    /// but it is not guaranteed to compile to identical resources.
+    pub fn to_rc_script(&self) -> String {
+        rc::write_rc_script(self)
+    }
+
+    /// Writes the compiled COFF object as a C source file containing a `const unsigned char embedinator_resources[]` array
+    /// and its length in `embedinator_resources_len`.
+    /// This allows embedding the resources with toolchains that can compile C but can't be driven through cargo.
+    ///
+    /// Resources that can't be compiled, see [`ResourceBuilder::try_compile`], are reported as [`std::io::ErrorKind::InvalidInput`].
+    pub fn write_c_source<P: AsRef<Path>>(&self, target: TargetType, path: P) -> std::io::Result<()> {
+        let data = self
+            .try_compile(target)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+
+        let mut source = String::new();
+        source.push_str("/* Generated by embedinator. Do not edit. */\n\n");
+        source.push_str("const unsigned char embedinator_resources[] = {\n");
+        for line in data.chunks(16) {
+            source.push_str("   ");
+            for byte in line {
+                write!(source, " 0x{byte:02x},").expect("Failed to format byte");
+            }
+            source.push('\n');
         }
+        source.push_str("};\n\n");
+        writeln!(source, "const unsigned long embedinator_resources_len = {};", data.len()).expect("Failed to format length");
+
+        std::fs::write(path, source)
     }
 
+    /// Writes the resources to `OUT_DIR` and tells cargo to link them.
+    ///
+    /// The file is always a bare COFF object, which both `link.exe` and GNU `ld` link as a whole.
+    /// It is named `resources.lib` for MSVC and `resources.o` for GNU targets, the extensions the linkers expect for their inputs,
+    /// unless a different name is set with [`ResourceBuilder::set_output_name`].
+    ///
+    /// A builder without any resources, see [`ResourceBuilder::is_empty`], only prints a warning and links nothing.
+    ///
+    /// # Panics
+    /// Panics if [`ResourceBuilder::try_finish`] fails.
     pub fn finish(self) {
-        let target = var("CARGO_CFG_TARGET_ARCH").expect("No CARGO_CFG_TARGET_ARCH env var");
-        let target = match target.as_str() {
-            "x86_64" => TargetType::X86_64,
-            "x86" => TargetType::I386,
-            "aarch64" => TargetType::Aarch64,
-            _ => panic!("Unsupported target arch")
+        if let Err(err) = self.try_finish() {
+            panic!("Failed to embed resources: {err}");
+        }
+    }
+
+    /// Like [`ResourceBuilder::finish`], but returns an error instead of panicking.
+    pub fn try_finish(self) -> Result<(), EmbedError> {
+        self.try_finish_returning().map(drop)
+    }
+
+    /// Like [`ResourceBuilder::finish`], but returns the files it wrote and linked, e.g. to keep them as build artifacts.
+    ///
+    /// Every entry is the path of an object in `OUT_DIR` together with its contents. There is one entry per object
+    /// with [`ResourceBuilder::split_objects`] and none if the builder [is empty](ResourceBuilder::is_empty).
+    ///
+    /// # Panics
+    /// Panics if [`ResourceBuilder::try_finish_returning`] fails.
+    pub fn finish_returning(self) -> Vec<(PathBuf, ResourceFile)> {
+        self.try_finish_returning()
+            .unwrap_or_else(|err| panic!("Failed to embed resources: {err}"))
+    }
+
+    /// Like [`ResourceBuilder::finish_returning`], but returns an error instead of panicking.
+    pub fn try_finish_returning(mut self) -> Result<Vec<(PathBuf, ResourceFile)>, EmbedError> {
+        let target = var("CARGO_CFG_TARGET_ARCH").map_err(|_| EmbedError::MissingEnvVar("CARGO_CFG_TARGET_ARCH"))?;
+        let target = TargetType::from_arch(&target).ok_or(EmbedError::UnsupportedArch(target))?;
+
+        let target_env = self
+            .target_env
+            .unwrap_or_else(|| match var("CARGO_CFG_TARGET_ENV").as_deref() {
+                Ok("gnu") => TargetEnv::Gnu,
+                _ => TargetEnv::Msvc
+            });
+
+        // Both linkers accept a plain COFF object, they only differ in the file extension they expect.
+        let out_dir = var("OUT_DIR").map_err(|_| EmbedError::MissingEnvVar("OUT_DIR"))?;
+        let name = self.output_name.as_deref().unwrap_or("resources");
+        let out_file = match Path::new(name).extension() {
+            Some(_) => format!("{out_dir}/{name}"),
+            None => format!("{out_dir}/{name}.{}", target_env.object_extension())
         };
 
-        let out_dir = var("OUT_DIR").expect("No OUT_DIR env var");
-        let out_file = format!("{out_dir}/resources.lib");
+        self.check_version_strings()?;
 
-        // COFF doesn't seem to work, idk why
-        //self.compile_to_res()
-        self.compile_to_coff(target)
-            .write_to_file(&out_file)
-            .expect("Failed to write resource file");
+        if self.is_empty() {
+            println!("cargo:warning=There are no resources to embed, so nothing is linked");
+            return Ok(Vec::new());
+        }
+
+        if self.verbose {
+            self.print_summary(target, target_env)?;
+        }
+
+        let out_files = match (self.split_objects, target_env) {
+            (true, TargetEnv::Gnu) => {
+                let path = Path::new(&out_file);
+                let (stem, extension) = (path.file_stem().unwrap_or_default(), path.extension().unwrap_or_default());
+                let mut out_files = Vec::new();
+                for (group, data) in self.try_compile_split(target)? {
+                    let out_file = path.with_file_name(format!("{}-{group}.{}", stem.to_string_lossy(), extension.to_string_lossy()));
+                    // leave unchanged objects alone, so their timestamps keep telling the truth
+                    if std::fs::read(&out_file).ok().as_ref() != Some(&data) {
+                        write_output(&out_file, &data)?;
+                    }
+                    out_files.push((out_file, data));
+                }
+                out_files
+            }
+            (split, _) => {
+                if split {
+                    println!("cargo:warning=The MSVC linker only accepts a single resource object, so the resources are not split");
+                }
+                let data = self.try_compile(target)?;
+                write_output(Path::new(&out_file), &data)?;
+                vec![(PathBuf::from(out_file), data)]
+            }
+        };
 
-        println!("cargo:rustc-link-arg-bins={}", &out_file);
+        if self.verbose {
+            for (out_file, data) in &out_files {
+                println!("cargo:warning=Wrote {} ({} bytes)", out_file.display(), data.len());
+            }
+        }
+
+        let crate_types = match self.crate_types.is_empty() {
+            true => BTreeSet::from([CrateType::Bin]),
+            false => std::mem::take(&mut self.crate_types)
+        };
+        for crate_type in &crate_types {
+            for (out_file, _) in &out_files {
+                println!("cargo:{}={}", crate_type.link_arg_directive(), out_file.display());
+            }
+        }
+        Ok(out_files
+            .into_iter()
+            .map(|(path, data)| {
+                let file = ResourceFile {
+                    data,
+                    kind: ResourceFileKind::Coff
+                };
+                (path, file)
+            })
+            .collect())
     }
+
+    /// Writes the resources as a COFF object for `target` to `path`.
+    ///
+    /// Unlike [`ResourceBuilder::finish`], this does not read any cargo environment variables or print any cargo directives,
+    /// which makes it usable from other build systems. The caller is responsible for passing the file to the linker.
+    /// Missing parent directories of `path` are created.
+    pub fn finish_to(self, path: &Path, target: TargetType) -> Result<(), EmbedError> {
+        write_output(path, &self.try_compile(target)?)
+    }
+
+    /// Compiles the resources into a COFF object for `target` in memory.
+    ///
+    /// This produces the same bytes that [`ResourceBuilder::finish`] would write, but without touching the file system
+    /// or the cargo environment. This makes it usable for tests on any host.
+    pub fn compile(&self, target: TargetType) -> Vec<u8> {
+        self.try_compile(target)
+            .unwrap_or_else(|err| panic!("Failed to compile resources: {err}"))
+    }
+
+    /// Like [`ResourceBuilder::compile`], but returns an error instead of panicking.
+    pub fn try_compile(&self, target: TargetType) -> Result<Vec<u8>, EmbedError> {
+        self.validate()?;
+        Ok(self.try_compile_to_coff(target)?.data)
+    }
+
+    /// The exact size of the object [`ResourceBuilder::compile`] produces for `target`.
+    ///
+    /// This is cheaper than compiling, as only the resource data is serialized and the directory and the rest of the object are
+    /// just measured. It is meant for build tooling that wants to keep an eye on the size the resources add to the binary.
+    ///
+    /// Returns the same errors as [`ResourceBuilder::try_compile`] for resources that can't be compiled.
+    pub fn compiled_size(&self, target: TargetType) -> Result<usize, EmbedError> {
+        self.validate()?;
+        let mut writer = CoffWriter::with_capacity(target, self.estimated_size());
+        self.add_to_coff(&mut writer, |_| true);
+        Ok(writer.size())
+    }
+
+    /// Prints the resources that are going to be embedded as cargo warnings, see [`ResourceBuilder::verbose`].
+    fn print_summary(&self, target: TargetType, target_env: TargetEnv) -> Result<(), EmbedError> {
+        // building the directory panics on problems that compiling reports as errors
+        self.validate()?;
+        let mut writer = CoffWriter::with_capacity(target, self.estimated_size());
+        self.add_to_coff(&mut writer, |_| true);
+        println!("cargo:warning=Embedding resources for {target:?} ({target_env:?})");
+        for (ty, id, language, size) in writer.entries() {
+            println!("cargo:warning=  {ty} {id}, language {:#06x}: {size} bytes", language.0);
+        }
+        Ok(())
+    }
+
+    /// A fingerprint of the resources for build caches, computed from the type, id, language and data of every resource.
+    ///
+    /// Unlike a hash of the compiled object, the fingerprint ignores the file date of the version info, so two builders
+    /// whose resources only differ in their timestamp, e.g. from [`ResourceBuilder::set_version_timestamp`], share a fingerprint.
+    /// It uses a fixed hash function, so it stays the same across builds and toolchains and can be persisted.
+    /// The resources don't depend on the target, so neither does the fingerprint. `target` is reserved for resources that might.
+    ///
+    /// Returns the same errors as [`ResourceBuilder::try_compile`] for resources that can't be compiled.
+    pub fn content_fingerprint(&self, target: TargetType) -> Result<u64, EmbedError> {
+        self.validate()?;
+        // FNV-1a, which unlike the standard library hashers is guaranteed to never change
+        fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+            bytes
+                .iter()
+                .fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+        }
+        let mut logical = self.clone();
+        for version in std::iter::once(&mut logical.version).chain(logical.localized_versions.values_mut()) {
+            version.file_date = 0;
+        }
+        let mut writer = CoffWriter::with_capacity(target, logical.estimated_size());
+        logical.add_to_coff(&mut writer, |_| true);
+        Ok(writer
+            .entries()
+            .zip(writer.entry_data())
+            .fold(0xcbf2_9ce4_8422_2325, |hash, ((ty, id, language, _), data)| {
+                let key = format!("{ty}/{id}/{:04x}/{}", language.0, data.len());
+                fnv1a(fnv1a(hash, key.as_bytes()), &data)
+            }))
+    }
+
+    /// Warns about version info that lacks the strings Explorer shows on the details tab, or fails in strict mode.
+    /// Version info without any strings is left alone, as it was most likely left empty on purpose.
+    fn check_version_strings(&self) -> Result<(), EmbedError> {
+        const EXPECTED: [&str; 4] = ["FileDescription", "ProductName", "FileVersion", "ProductVersion"];
+        for version in std::iter::once(&self.version).chain(self.localized_versions.values()) {
+            if version.strings.is_empty() {
+                continue;
+            }
+            for key in EXPECTED {
+                if version.strings.contains_key(key) {
+                    continue;
+                }
+                if self.strict {
+                    return Err(EmbedError::MissingVersionString(key));
+                }
+                println!("cargo:warning=The version info has no {key:?} string");
+            }
+        }
+        Ok(())
+    }
+
+    /// The language and code page of every version info with a string table, which the `Translation` of each version info lists.
+    pub(crate) fn version_translations(&self) -> Vec<(LanguageId, u16)> {
+        std::iter::once((LanguageId::LANG_US, &self.version))
+            .chain(
+                self.localized_versions
+                    .iter()
+                    .map(|(language, version)| (*language, version))
+            )
+            .filter(|(_, version)| !version.strings.is_empty())
+            .map(|(language, version)| (language, version.effective_codepage()))
+            .collect()
+    }
+
+    /// Checks for problems that would otherwise only surface as a panic while writing the resources.
+    fn validate(&self) -> Result<(), EmbedError> {
+        let translations = self.version_translations().len();
+        for version in std::iter::once(&self.version).chain(self.localized_versions.values()) {
+            if let Some(key) = version.find_overlong_string(translations) {
+                return Err(EmbedError::VersionStringTooLong { key: key.to_string() });
+            }
+            FileFlags::from_iter(version.flags.iter().copied()).validate(&version.strings)?;
+        }
+        // the add methods already reject collisions, but a deserialized builder never went through them
+        let mut keys = BTreeSet::new();
+        if let Some((ty, id, language)) = self.resource_keys().find(|key| !keys.insert(*key)) {
+            return Err(EmbedError::DuplicateResource {
+                ty: ty.to_string(),
+                id,
+                language
+            });
+        }
+        let mut names = BTreeSet::new();
+        if let Some((ty, name, _)) = self
+            .named_resources
+            .iter()
+            .find(|(ty, name, _)| !names.insert((*ty, name)))
+        {
+            return Err(EmbedError::DuplicateNamedResource {
+                ty: ty.to_string(),
+                name: name.clone()
+            });
+        }
+        for (group, language, entries) in &self.icon_groups {
+            let missing = entries.iter().find(|entry| {
+                !self
+                    .icons
+                    .iter()
+                    .any(|(id, l, _)| *id == entry.icon_id && l == language)
+            });
+            if let Some(entry) = missing {
+                return Err(EmbedError::MissingIconImage {
+                    group: *group,
+                    image: entry.icon_id,
+                    language: *language
+                });
+            }
+        }
+        if let Some((_, manifest)) = self.manifest.as_ref().filter(|_| !self.manifest_unchecked) {
+            // raw manifests may use any encoding, which only matters for the text, not for the markup
+            manifest::check_well_formed(&String::from_utf8_lossy(manifest), !self.manifest_raw).map_err(EmbedError::InvalidManifest)?;
+        }
+        for (_, manifest) in &self.activation_manifests {
+            manifest::check_well_formed(&String::from_utf8_lossy(manifest), true).map_err(EmbedError::InvalidManifest)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned by [`ResourceBuilder::try_finish`] and [`ResourceBuilder::finish_to`].
+#[derive(Debug)]
+pub enum EmbedError {
+    /// Writing the resource file failed.
+    Io(std::io::Error),
+    /// A cargo environment variable is not set, most likely because the builder is not used from a build script.
+    MissingEnvVar(&'static str),
+    /// The package version has a component that doesn't fit into the version info, like the 70000 of `1.0.70000`.
+    InvalidPackageVersion { version: String, error: ParseVersionError },
+    /// The target architecture is not supported.
+    UnsupportedArch(String),
+    /// The version strings exceed the 64 KiB limit of the version info. `key` is the string that crossed the limit.
+    VersionStringTooLong { key: String },
+    /// The manifest is not well-formed XML.
+    InvalidManifest(ManifestError),
+    /// The named part of the COFF object exceeds the limits of its 32-bit offsets or 16-bit counts.
+    ObjectTooLarge(&'static str),
+    /// The version info lacks a conventional string, only reported in [strict mode](ResourceBuilder::strict).
+    MissingVersionString(&'static str),
+    /// A file flag is set without the version string that has to accompany it.
+    MissingFlagString { flag: FileFlag, key: &'static str },
+    /// Two resources share the same type, id and language. `ty` is the numeric type id or the name of a named type.
+    DuplicateResource { ty: String, id: u16, language: LanguageId },
+    /// There is more than one file with the same type and name, see [`ResourceBuilder::add_file`].
+    DuplicateNamedResource { ty: String, name: String },
+    /// An icon group refers to an image that is not part of the resources.
+    MissingIconImage { group: u16, image: u16, language: LanguageId },
+    /// The file passed to [`ResourceBuilder::try_add_res_file`] is not a valid RES file.
+    InvalidResFile(parse::ParseError),
+    /// A resource of a RES file has a type or name the builder can't represent, see [`ResourceBuilder::try_add_res_file`].
+    UnsupportedResource { ty: String, name: String }
+}
+
+impl Display for EmbedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmbedError::Io(error) => write!(f, "failed to write resource file: {error}"),
+            EmbedError::MissingEnvVar(name) => write!(f, "no {name} env var"),
+            EmbedError::InvalidPackageVersion { version, error } => {
+                write!(f, "the package version {version:?} does not fit into the version info: {error}")
+            }
+            EmbedError::UnsupportedArch(arch) => write!(f, "unsupported target arch {arch:?}"),
+            EmbedError::VersionStringTooLong { key } => {
+                write!(f, "version string {key:?} is too long, all version strings together must fit into 64 KiB")
+            }
+            EmbedError::InvalidManifest(error) => write!(f, "{error}"),
+            EmbedError::ObjectTooLarge(part) => write!(f, "the {part} does not fit into a COFF object"),
+            EmbedError::MissingVersionString(key) => write!(f, "the version info has no {key:?} string"),
+            EmbedError::MissingFlagString { flag, key } => write!(f, "the {flag:?} file flag requires a {key:?} version string"),
+            EmbedError::DuplicateResource { ty, id, language } => {
+                write!(
+                    f,
+                    "there is more than one resource of type {ty} with id {id} and language {:#06x}",
+                    language.0
+                )
+            }
+            EmbedError::DuplicateNamedResource { ty, name } => {
+                write!(f, "there is more than one resource of type {ty} with name {name:?}")
+            }
+            EmbedError::MissingIconImage { group, image, language } => {
+                write!(f, "icon {group} in language {:#06x} refers to the missing image {image}", language.0)
+            }
+            EmbedError::InvalidResFile(error) => write!(f, "failed to parse RES file: {error}"),
+            EmbedError::UnsupportedResource { ty, name } => write!(f, "the resource of type {ty} with name {name} is not supported")
+        }
+    }
+}
+
+impl Error for EmbedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EmbedError::Io(error) => Some(error),
+            EmbedError::InvalidManifest(error) => Some(error),
+            EmbedError::InvalidResFile(error) => Some(error),
+            EmbedError::InvalidPackageVersion { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<std::io::Error> for EmbedError {
+    fn from(error: std::io::Error) -> Self {
+        EmbedError::Io(error)
+    }
+}
+
+/// Inserts `item` behind all items with a smaller or equal key, so the resources are written in the same order
+/// no matter in which order they were added.
+fn insert_sorted<T, K: Ord>(items: &mut Vec<T>, item: T, key: impl Fn(&T) -> K) {
+    let index = items.partition_point(|i| key(i) <= key(&item));
+    items.insert(index, item);
+}
+
+/// Replaces the fourth component of the dotted version number at the start of `text`.
+fn with_build_number(text: &str, build: u16) -> String {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, suffix) = text.split_at(end);
+    let mut components: Vec<&str> = number.split('.').collect();
+    if components.iter().any(|c| c.is_empty()) {
+        return text.to_string();
+    }
+    components.resize(3, "0");
+    format!("{}.{build}{suffix}", components.join("."))
+}
+
+/// The package metadata that cargo passes to build scripts.
+struct PackageEnv {
+    version: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    homepage: Option<String>,
+    authors: Option<String>,
+    library: Option<LibraryKind>,
+    source_date_epoch: Option<u64>
+}
+
+// only detected with the `toml` feature
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(not(feature = "toml"), allow(dead_code))]
+enum LibraryKind {
+    Cdylib,
+    Dylib
+}
+
+impl PackageEnv {
+    fn read() -> Self {
+        println!("cargo:rerun-if-env-changed=CARGO_PKG_VERSION");
+        println!("cargo:rerun-if-env-changed=CARGO_PKG_NAME");
+        println!("cargo:rerun-if-env-changed=CARGO_PKG_DESCRIPTION");
+        println!("cargo:rerun-if-env-changed=CARGO_PKG_HOMEPAGE");
+        println!("cargo:rerun-if-env-changed=CARGO_PKG_AUTHORS");
+        println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+        Self {
+            version: var("CARGO_PKG_VERSION").ok(),
+            name: var("CARGO_PKG_NAME").ok(),
+            description: var("CARGO_PKG_DESCRIPTION").ok(),
+            // cargo sets these to an empty string if the manifest doesn't have them
+            homepage: var("CARGO_PKG_HOMEPAGE").ok().filter(|h| !h.is_empty()),
+            authors: var("CARGO_PKG_AUTHORS").ok().filter(|a| !a.is_empty()),
+            #[cfg(feature = "toml")]
+            library: var("CARGO_MANIFEST_DIR")
+                .ok()
+                .and_then(|dir| dynamic_library_kind(Path::new(&dir))),
+            #[cfg(not(feature = "toml"))]
+            library: None,
+            source_date_epoch: var("SOURCE_DATE_EPOCH")
+                .ok()
+                .and_then(|epoch| epoch.parse().ok())
+        }
+    }
+}
+
+fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The group of a resource in [`ResourceBuilder::compile_split`].
+fn object_group(ty: ResourceType) -> &'static str {
+    match ty {
+        ResourceType::Version => "version",
+        ResourceType::Icon | ResourceType::IconGroup => "icons",
+        ResourceType::Manifest => "manifest",
+        _ => "other"
+    }
+}
+
+/// Returns the name of the first author of a `CARGO_PKG_AUTHORS` list like `Jane Doe <jane@example.com>:John Doe`.
+fn first_author(authors: &str) -> Option<&str> {
+    let author = authors.split(':').next()?;
+    let name = author
+        .split_once('<')
+        .map_or(author, |(name, _)| name)
+        .trim();
+    Some(name).filter(|name| !name.is_empty())
+}
+
+/// Checks whether the package builds a dynamic library and no binaries.
+///
+/// Cargo doesn't tell build scripts which crate types are built, so this reads them from the manifest instead.
+/// Packages that build both binaries and a library are left alone, as the resources can only describe one of them.
+#[cfg(feature = "toml")]
+fn dynamic_library_kind(manifest_dir: &Path) -> Option<LibraryKind> {
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    rerun_if_changed(&manifest_path);
+    let manifest = std::fs::read_to_string(&manifest_path).ok()?;
+    let has_bin_files = manifest_dir.join("src/main.rs").exists() || manifest_dir.join("src/bin").is_dir();
+    manifest_library_kind(&manifest, has_bin_files)
+}
+
+/// The part of [`dynamic_library_kind`] that looks at the contents of the manifest.
+#[cfg(feature = "toml")]
+fn manifest_library_kind(manifest: &str, has_bin_files: bool) -> Option<LibraryKind> {
+    let manifest: toml::Table = manifest.parse().ok()?;
+    let has_bins = has_bin_files
+        || manifest
+            .get("bin")
+            .and_then(toml::Value::as_array)
+            .is_some_and(|bins| !bins.is_empty());
+    let lib = manifest.get("lib")?.as_table()?;
+    let crate_types = lib
+        .get("crate-type")
+        .or_else(|| lib.get("crate_type"))?
+        .as_array()?;
+    let has_type = |ty: &str| crate_types.iter().any(|t| t.as_str() == Some(ty));
+    let library = if has_type("cdylib") {
+        LibraryKind::Cdylib
+    } else if has_type("dylib") {
+        LibraryKind::Dylib
+    } else {
+        return None;
+    };
+    Some(library).filter(|_| !has_bins)
 }
 
-#[doc(hidden)]
+/// Writes a compiled object, creating the missing parent directories.
+/// Errors keep their kind, but mention the path, as a bare "permission denied" says little in the output of a build script.
+fn write_output(path: &Path, data: &[u8]) -> Result<(), EmbedError> {
+    let with_path = |err: std::io::Error| EmbedError::Io(std::io::Error::new(err.kind(), format!("{}: {err}", path.display())));
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent).map_err(with_path)?;
+    }
+    std::fs::write(path, data).map_err(with_path)
+}
+
+/// Tells cargo to rerun the build script if the file changes. Does nothing outside of build scripts.
+fn rerun_if_changed(path: &Path) {
+    if std::env::var_os("OUT_DIR").is_some() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+/// The format of a [`ResourceFile`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ResourceFileKind {
+    /// A COFF object file that can be passed to the linker directly.
+    /// This is what [`ResourceBuilder::finish`] passes to both `link.exe` and GNU `ld`, regardless of the file extension.
     Coff,
+    /// A COFF archive (static library) whose only member is the COFF object.
+    ///
+    /// Linkers only take members out of an archive to resolve undefined symbols, which the resources never are,
+    /// so an archive has to be linked with `/WHOLEARCHIVE` or `--whole-archive` to actually embed the resources.
+    Archive,
+    /// A RES file as produced by `rc.exe`.
+    /// It starts with an empty resource, followed by one header and data block per resource, each aligned to four bytes.
     Res
 }
 
-#[doc(hidden)]
+/// A compiled resource file.
 #[must_use]
 #[derive(Clone, Eq, PartialEq)]
 pub struct ResourceFile {
@@ -319,7 +2829,38 @@ pub struct ResourceFile {
 }
 
 impl ResourceFile {
+    /// The raw bytes of the compiled file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Writes the compiled file to an arbitrary writer, e.g. an in-memory buffer or a hasher.
+    pub fn write_to<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        w.write_all(&self.data)
+    }
+
+    /// Writes the compiled file to `path`.
+    ///
+    /// A COFF object written to a `.lib` or `.a` file is wrapped in an archive first, see [`ResourceFile::to_archive`].
+    /// Use any other extension, like `.obj` or `.o`, to get the bare object.
     pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
-        std::fs::write(path, &self.data)
+        let path = path.as_ref();
+        let is_archive = matches!(path.extension().and_then(|e| e.to_str()), Some("lib" | "a"));
+        let mut file = std::fs::File::create(path)?;
+        match self.kind {
+            ResourceFileKind::Coff if is_archive => self.to_archive().write_to(&mut file),
+            _ => self.write_to(&mut file)
+        }
+    }
+
+    /// Wraps a COFF object in an archive with a single member. Other kinds of files are returned unchanged.
+    pub fn to_archive(&self) -> ResourceFile {
+        match self.kind {
+            ResourceFileKind::Coff => ResourceFile {
+                data: coff::write_archive("resources.obj", &self.data),
+                kind: ResourceFileKind::Archive
+            },
+            _ => self.clone()
+        }
     }
 }
@@ -0,0 +1,360 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+
+/// Generates the usual application manifest, so no `.manifest` file has to be shipped.
+///
+/// Everything is off by default, which produces a manifest with an empty `assembly` element.
+/// The output only depends on the settings and not on the order in which they were made.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ManifestBuilder {
+    dpi_awareness: Option<DpiAwareness>,
+    long_path_aware: bool,
+    utf8: bool,
+    execution_level: Option<ExecutionLevel>,
+    supported_os: BTreeSet<WindowsVersion>,
+    package_identity: Option<PackageIdentity>
+}
+
+/// The identity of the package an unpackaged application is associated with, see [`ManifestBuilder::package_identity`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PackageIdentity {
+    publisher: String,
+    package_name: String,
+    application_id: String
+}
+
+/// How the application handles different display scaling factors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DpiAwareness {
+    /// The application is always scaled by the system.
+    Unaware,
+    /// The application scales itself to the DPI of the primary monitor.
+    System,
+    /// The application scales itself whenever the DPI of its monitor changes.
+    PerMonitor,
+    /// Like [`DpiAwareness::PerMonitor`], but the system also scales the non-client area and common controls.
+    /// Falls back to [`DpiAwareness::PerMonitor`] on systems older than Windows 10 1703.
+    PerMonitorV2
+}
+
+/// The privileges the application requests when it is started.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExecutionLevel {
+    /// Runs with the privileges of the parent process.
+    AsInvoker,
+    /// Runs with the highest privileges the current user can get.
+    HighestAvailable,
+    /// Always requires administrator privileges.
+    RequireAdministrator
+}
+
+/// A Windows version for the `supportedOS` compatibility list.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowsVersion {
+    Vista,
+    Windows7,
+    Windows8,
+    Windows81,
+    /// Windows 10 and Windows 11, which share their id.
+    Windows10
+}
+
+impl WindowsVersion {
+    fn guid(self) -> &'static str {
+        match self {
+            WindowsVersion::Vista => "{e2011457-1546-43c5-a5fe-008deee3d3f0}",
+            WindowsVersion::Windows7 => "{35138b9a-5d96-4fbd-8e2d-a2440225f93a}",
+            WindowsVersion::Windows8 => "{4a2f28e3-53b9-4441-ba9c-d69d4a4a6e38}",
+            WindowsVersion::Windows81 => "{1f676c76-80e1-4239-95bb-83d0f6d0da78}",
+            WindowsVersion::Windows10 => "{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}"
+        }
+    }
+}
+
+impl ExecutionLevel {
+    fn name(self) -> &'static str {
+        match self {
+            ExecutionLevel::AsInvoker => "asInvoker",
+            ExecutionLevel::HighestAvailable => "highestAvailable",
+            ExecutionLevel::RequireAdministrator => "requireAdministrator"
+        }
+    }
+}
+
+impl ManifestBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dpi_awareness(mut self, dpi_awareness: DpiAwareness) -> Self {
+        self.dpi_awareness = Some(dpi_awareness);
+        self
+    }
+
+    /// Allows paths longer than `MAX_PATH`. This also has to be enabled in the registry to have an effect.
+    pub fn long_path_aware(mut self, long_path_aware: bool) -> Self {
+        self.long_path_aware = long_path_aware;
+        self
+    }
+
+    /// Makes UTF-8 the active code page of the process, so the `A` variants of the Windows APIs accept UTF-8.
+    pub fn utf8(mut self, utf8: bool) -> Self {
+        self.utf8 = utf8;
+        self
+    }
+
+    pub fn requested_execution_level(mut self, level: ExecutionLevel) -> Self {
+        self.execution_level = Some(level);
+        self
+    }
+
+    pub fn supported_os(mut self, versions: &[WindowsVersion]) -> Self {
+        self.supported_os.extend(versions);
+        self
+    }
+
+    /// Associates the application with an MSIX package, which grants it package identity without being packaged itself.
+    /// The values have to match the `Identity` and `Application` elements of the package manifest.
+    /// This requires Windows 10 2004 or later.
+    pub fn package_identity(mut self, publisher: &str, package_name: &str, application_id: &str) -> Self {
+        self.package_identity = Some(PackageIdentity {
+            publisher: publisher.to_string(),
+            package_name: package_name.to_string(),
+            application_id: application_id.to_string()
+        });
+        self
+    }
+
+    /// Generates the manifest XML.
+    pub fn build(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+        xml.push_str("<assembly xmlns=\"urn:schemas-microsoft-com:asm.v1\" manifestVersion=\"1.0\">\n");
+        if let Some(identity) = &self.package_identity {
+            writeln!(
+                xml,
+                "  <msix xmlns=\"urn:schemas-microsoft-com:msix.v1\" publisher=\"{}\" packageName=\"{}\" applicationId=\"{}\"/>",
+                escape_attribute(&identity.publisher),
+                escape_attribute(&identity.package_name),
+                escape_attribute(&identity.application_id)
+            )
+            .expect("Failed to format manifest");
+        }
+        if let Some(level) = self.execution_level {
+            xml.push_str("  <trustInfo xmlns=\"urn:schemas-microsoft-com:asm.v3\">\n");
+            xml.push_str("    <security>\n");
+            xml.push_str("      <requestedPrivileges>\n");
+            writeln!(xml, "        <requestedExecutionLevel level=\"{}\" uiAccess=\"false\"/>", level.name()).expect("Failed to format manifest");
+            xml.push_str("      </requestedPrivileges>\n");
+            xml.push_str("    </security>\n");
+            xml.push_str("  </trustInfo>\n");
+        }
+        if !self.supported_os.is_empty() {
+            xml.push_str("  <compatibility xmlns=\"urn:schemas-microsoft-com:compatibility.v1\">\n");
+            xml.push_str("    <application>\n");
+            for version in &self.supported_os {
+                writeln!(xml, "      <supportedOS Id=\"{}\"/>", version.guid()).expect("Failed to format manifest");
+            }
+            xml.push_str("    </application>\n");
+            xml.push_str("  </compatibility>\n");
+        }
+        if self.dpi_awareness.is_some() || self.long_path_aware || self.utf8 {
+            xml.push_str("  <application xmlns=\"urn:schemas-microsoft-com:asm.v3\">\n");
+            xml.push_str("    <windowsSettings>\n");
+            if let Some(dpi_awareness) = self.dpi_awareness {
+                // dpiAware is read by systems before Windows 10 1607, which ignore dpiAwareness
+                let (legacy, current) = match dpi_awareness {
+                    DpiAwareness::Unaware => ("false", "unaware"),
+                    DpiAwareness::System => ("true", "system"),
+                    DpiAwareness::PerMonitor => ("true/pm", "PerMonitor"),
+                    DpiAwareness::PerMonitorV2 => ("true/pm", "PerMonitorV2, PerMonitor")
+                };
+                writeln!(xml, "      <dpiAware xmlns=\"{SMI_2005}\">{legacy}</dpiAware>").expect("Failed to format manifest");
+                writeln!(xml, "      <dpiAwareness xmlns=\"{SMI_2016}\">{current}</dpiAwareness>").expect("Failed to format manifest");
+            }
+            if self.long_path_aware {
+                writeln!(xml, "      <longPathAware xmlns=\"{SMI_2016}\">true</longPathAware>").expect("Failed to format manifest");
+            }
+            if self.utf8 {
+                writeln!(xml, "      <activeCodePage xmlns=\"{SMI_2019}\">UTF-8</activeCodePage>").expect("Failed to format manifest");
+            }
+            xml.push_str("    </windowsSettings>\n");
+            xml.push_str("  </application>\n");
+        }
+        xml.push_str("</assembly>\n");
+        xml
+    }
+}
+
+/// Escapes the characters that can't appear as they are in a double-quoted attribute value.
+fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+const SMI_2005: &str = "http://schemas.microsoft.com/SMI/2005/WindowsSettings";
+const SMI_2016: &str = "http://schemas.microsoft.com/SMI/2016/WindowsSettings";
+const SMI_2019: &str = "http://schemas.microsoft.com/SMI/2019/WindowsSettings";
+
+/// The error returned when a manifest is not well-formed XML.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ManifestError {
+    /// The line of the problem, starting at 1.
+    pub line: usize,
+    /// The column of the problem in characters, starting at 1.
+    pub column: usize,
+    pub reason: String
+}
+
+impl ManifestError {
+    fn new(xml: &str, offset: usize, reason: impl Into<String>) -> Self {
+        let before = &xml[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            reason: reason.into()
+        }
+    }
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid manifest at line {}, column {}: {}", self.line, self.column, self.reason)
+    }
+}
+
+impl Error for ManifestError {}
+
+/// Checks that the manifest looks like well-formed XML.
+///
+/// This is far from a full XML parser, it only catches the mistakes that would otherwise surface as a
+/// "side-by-side configuration is incorrect" error at launch: unbalanced tags, a missing or duplicated root element
+/// and an XML declaration with an encoding other than UTF-8 if the manifest is embedded as UTF-8.
+pub(crate) fn check_well_formed(xml: &str, embedded_as_utf8: bool) -> Result<(), ManifestError> {
+    let error = |offset: usize, reason: String| Err(ManifestError::new(xml, offset, reason));
+
+    let start = xml
+        .strip_prefix('\u{feff}')
+        .map_or(0, |_| '\u{feff}'.len_utf8());
+    let mut pos = start;
+    let mut open: Vec<(usize, &str)> = Vec::new();
+    let mut seen_root = false;
+
+    while pos < xml.len() {
+        let rest = &xml[pos..];
+        let Some(next) = rest.find('<') else {
+            if !rest.trim().is_empty() && open.is_empty() {
+                return error(pos, "text outside of the root element".into());
+            }
+            break;
+        };
+        if open.is_empty() && !rest[..next].trim().is_empty() {
+            return error(pos, "text outside of the root element".into());
+        }
+        pos += next;
+        let rest = &xml[pos..];
+
+        let (terminator, description) = if rest.starts_with("<?") {
+            ("?>", "processing instruction")
+        } else if rest.starts_with("<!--") {
+            ("-->", "comment")
+        } else if rest.starts_with("<![CDATA[") {
+            ("]]>", "CDATA section")
+        } else if rest.starts_with("<!") {
+            (">", "declaration")
+        } else {
+            let end = match find_tag_end(rest) {
+                Some(end) => end,
+                None => return error(pos, "unterminated tag".into())
+            };
+            let tag = &rest[1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim_end();
+                match open.pop() {
+                    Some((_, expected)) if expected == name => {}
+                    Some((_, expected)) => return error(pos, format!("expected </{expected}>, found </{name}>")),
+                    None => return error(pos, format!("unexpected </{name}>"))
+                }
+            } else {
+                let self_closing = tag.ends_with('/');
+                let name = tag
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or_default();
+                if name.is_empty() {
+                    return error(pos, "missing element name".into());
+                }
+                if open.is_empty() {
+                    if seen_root {
+                        return error(pos, format!("second root element <{name}>"));
+                    }
+                    seen_root = true;
+                }
+                if !self_closing {
+                    open.push((pos, name));
+                }
+            }
+            pos += end + 1;
+            continue;
+        };
+
+        let Some(end) = rest.find(terminator) else {
+            return error(pos, format!("unterminated {description}"));
+        };
+        if rest.starts_with("<?xml") && rest[5..].starts_with(|c: char| c.is_whitespace() || c == '?') {
+            if pos != start {
+                return error(pos, "the XML declaration must be at the very start".into());
+            }
+            if let Some(encoding) = declared_encoding(&rest[..end]).filter(|_| embedded_as_utf8) {
+                if !encoding.eq_ignore_ascii_case("utf-8") {
+                    return error(pos, format!("the manifest is embedded as UTF-8, but declares encoding {encoding:?}"));
+                }
+            }
+        }
+        pos += end + terminator.len();
+    }
+
+    match open.last() {
+        Some(&(offset, name)) => error(offset, format!("<{name}> is never closed")),
+        None if !seen_root => error(xml.len(), "missing root element".into()),
+        None => Ok(())
+    }
+}
+
+/// Finds the closing `>` of a tag, skipping over quoted attribute values.
+fn find_tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            (None, '<') if i > 0 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn declared_encoding(declaration: &str) -> Option<&str> {
+    let value = declaration.split_once("encoding")?.1.trim_start();
+    let value = value.strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    value[1..].split(quote).next()
+}
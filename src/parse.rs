@@ -0,0 +1,1048 @@
+//! Parsers for the files produced by this crate.
+//!
+//! These are mainly intended for verifying the output of the writers, but can also be used to inspect or diff existing files.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::{RelocationType, ResourceType, TargetType, Version};
+
+/// The error returned when a file can not be parsed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    /// The file ended while reading a structure at the given offset.
+    UnexpectedEnd { offset: usize },
+    /// The file contains a structure that is not valid.
+    Invalid(&'static str)
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEnd { offset } => write!(f, "unexpected end of file at offset {offset:#x}"),
+            ParseError::Invalid(msg) => write!(f, "invalid file: {msg}")
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// A cursor over a little-endian byte buffer.
+#[derive(Debug, Clone)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub fn align_to(&mut self, i: usize) {
+        self.pos += (i - (self.pos % i)) % i;
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(ParseError::UnexpectedEnd { offset: self.pos })?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, ParseError> {
+        Ok(u8::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, ParseError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, ParseError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a null-terminated UTF-16 string.
+    pub fn read_utf16(&mut self) -> Result<String, ParseError> {
+        let mut chars = Vec::new();
+        loop {
+            match self.read_u16()? {
+                0 => break,
+                c => chars.push(c)
+            }
+        }
+        String::from_utf16(&chars).map_err(|_| ParseError::Invalid("string is not valid UTF-16"))
+    }
+}
+
+/// The contents of a COFF object file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedResources {
+    pub machine: u16,
+    pub timestamp: u32,
+    pub characteristics: u16,
+    pub sections: Vec<ParsedSection>,
+    pub symbols: Vec<ParsedSymbol>,
+    /// The resource directory, if the file contains a `.rsrc$01` or `.rsrc` section.
+    pub directory: Option<ResourceDirectory>
+}
+
+impl ParsedResources {
+    /// The target of the object, if its machine type is supported.
+    pub fn target(&self) -> Option<TargetType> {
+        TargetType::from_machine(self.machine)
+    }
+
+    /// Flattens the resource directory into a list of resources.
+    pub fn resources(&self) -> Vec<ParsedResource> {
+        self.directory
+            .as_ref()
+            .map(flatten_directory)
+            .unwrap_or_default()
+    }
+
+    /// The layout of the resource directory, see [`ResourceTree`].
+    pub fn tree(&self) -> ResourceTree {
+        let mut tree = ResourceTree::default();
+        for ty in self.directory.iter().flat_map(|d| &d.entries) {
+            let names = tree.types.entry(ty.name.clone()).or_default();
+            for name in subdirectory(ty) {
+                let languages = names.entry(name.name.clone()).or_default();
+                for language in subdirectory(name) {
+                    if let ResourceDirectoryEntryKind::Data(data) = &language.kind {
+                        let entry = ResourceTreeEntry {
+                            offset: data.offset,
+                            size: data.size,
+                            code_page: data.code_page
+                        };
+                        languages.insert(language.name.id().unwrap_or_default() as u16, entry);
+                    }
+                }
+            }
+        }
+        tree
+    }
+
+    /// Checks the invariants a linker relies on that parsing alone doesn't enforce.
+    ///
+    /// Every relocation of the table section must be an RVA relocation of the target that patches exactly one data entry,
+    /// the section symbols must agree with their sections, and the entries of every directory level must be sorted.
+    pub fn verify(&self) -> Result<(), ParseError> {
+        let target = self
+            .target()
+            .ok_or(ParseError::Invalid("unsupported machine type"))?;
+
+        for symbol in &self.symbols {
+            let Some(aux) = &symbol.section_aux else {
+                continue;
+            };
+            let section = symbol_section(&self.sections, symbol).ok_or(ParseError::Invalid("symbol points to a non-existing section"))?;
+            if aux.length as usize != section.data.len() || aux.number_of_relocations as usize != section.relocations.len() {
+                return Err(ParseError::Invalid("section symbol does not match its section"));
+            }
+        }
+
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
+        check_sorted(directory)?;
+
+        let table_section = self
+            .sections
+            .iter()
+            .find(|s| s.name == ".rsrc$01" || s.name == ".rsrc")
+            .ok_or(ParseError::Invalid("resource directory without table section"))?;
+        let mut entries = Vec::new();
+        data_entry_offsets(&table_section.data, 0, &mut entries)?;
+        entries.sort_unstable();
+        let mut patched: Vec<_> = table_section
+            .relocations
+            .iter()
+            .map(|rel| match RelocationType::from_id(target, rel.kind) {
+                Some(RelocationType::Rva32) => Ok(rel.virtual_address as usize),
+                _ => Err(ParseError::Invalid("resource relocation is not an RVA relocation"))
+            })
+            .collect::<Result<_, _>>()?;
+        patched.sort_unstable();
+        if patched != entries {
+            return Err(ParseError::Invalid("relocations do not match the resource data entries"));
+        }
+        Ok(())
+    }
+}
+
+/// The entries of the subdirectory an entry points to, if it points to one.
+fn subdirectory(entry: &ResourceDirectoryEntry) -> &[ResourceDirectoryEntry] {
+    match &entry.kind {
+        ResourceDirectoryEntryKind::Directory(directory) => &directory.entries,
+        ResourceDirectoryEntryKind::Data(_) => &[]
+    }
+}
+
+/// Checks that named entries come before numbered ones and that both are in ascending order without duplicates.
+fn check_sorted(directory: &ResourceDirectory) -> Result<(), ParseError> {
+    let key = |name: &ResourceName| match name {
+        ResourceName::Name(name) => (0, name.to_uppercase(), 0),
+        ResourceName::Id(id) => (1, String::new(), *id)
+    };
+    let sorted = directory
+        .entries
+        .windows(2)
+        .all(|pair| key(&pair[0].name) < key(&pair[1].name));
+    if !sorted {
+        return Err(ParseError::Invalid("resource directory entries are not sorted"));
+    }
+    directory
+        .entries
+        .iter()
+        .try_for_each(|entry| match &entry.kind {
+            ResourceDirectoryEntryKind::Directory(directory) => check_sorted(directory),
+            ResourceDirectoryEntryKind::Data(_) => Ok(())
+        })
+}
+
+/// Collects the offsets of the data entries below the directory at the given offset of the table.
+fn data_entry_offsets(table: &[u8], offset: usize, offsets: &mut Vec<usize>) -> Result<(), ParseError> {
+    let mut r = Reader::at(table, offset + 12);
+    let number_of_entries = r.read_u16()? as usize + r.read_u16()? as usize;
+    for _ in 0..number_of_entries {
+        let _name = r.read_u32()?;
+        let target = r.read_u32()?;
+        match target & SUBDIRECTORY_BIT {
+            0 => offsets.push(target as usize),
+            // cycles, shared and deeply nested directories have already been rejected while parsing
+            _ => data_entry_offsets(table, (target & !SUBDIRECTORY_BIT) as usize, offsets)?
+        }
+    }
+    Ok(())
+}
+
+fn flatten_directory(directory: &ResourceDirectory) -> Vec<ParsedResource> {
+    let mut resources = Vec::new();
+    for ty in &directory.entries {
+        let ResourceDirectoryEntryKind::Directory(names) = &ty.kind else {
+            continue;
+        };
+        for name in &names.entries {
+            let ResourceDirectoryEntryKind::Directory(languages) = &name.kind else {
+                continue;
+            };
+            for language in &languages.entries {
+                let ResourceDirectoryEntryKind::Data(data) = &language.kind else {
+                    continue;
+                };
+                resources.push(ParsedResource {
+                    ty: ty.name.clone(),
+                    name: name.name.clone(),
+                    language: language.name.id().unwrap_or_default() as u16,
+                    code_page: data.code_page,
+                    data: data.data.clone()
+                });
+            }
+        }
+    }
+    resources
+}
+
+/// A section of a COFF object file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedSection {
+    pub name: String,
+    pub characteristics: u32,
+    pub data: Vec<u8>,
+    pub relocations: Vec<ParsedRelocation>
+}
+
+/// A relocation of a section.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ParsedRelocation {
+    /// The offset of the relocated value relative to the start of the section.
+    pub virtual_address: u32,
+    pub symbol_index: u32,
+    pub kind: u16
+}
+
+impl ParsedRelocation {
+    /// Interprets the architecture specific `kind` for the given target, see [`ParsedResources::target`].
+    pub fn relocation_type(&self, target: TargetType) -> Option<RelocationType> {
+        RelocationType::from_id(target, self.kind)
+    }
+}
+
+/// An entry in the symbol table.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedSymbol {
+    /// The index in the symbol table. Auxiliary symbols occupy indices as well.
+    pub index: u32,
+    pub name: String,
+    pub value: u32,
+    pub section_number: i16,
+    pub ty: u16,
+    pub storage_class: u8,
+    /// The section definition of section symbols.
+    pub section_aux: Option<ParsedSectionAux>
+}
+
+/// The auxiliary symbol record of a section symbol.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ParsedSectionAux {
+    pub length: u32,
+    pub number_of_relocations: u16,
+    pub number_of_line_numbers: u16,
+    pub checksum: u32,
+    pub number: u16,
+    pub selection: u8
+}
+
+/// The name of a resource directory entry.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ResourceName {
+    Id(u32),
+    Name(String)
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ResourceName {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // plain numbers and strings, so the names also work as the keys of a map
+        match self {
+            ResourceName::Id(id) => serializer.serialize_u32(*id),
+            ResourceName::Name(name) => serializer.serialize_str(name)
+        }
+    }
+}
+
+/// Ids are shown as plain numbers and names quoted, like `24` and `"TYPELIB"`.
+impl Display for ResourceName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceName::Id(id) => write!(f, "{id}"),
+            ResourceName::Name(name) => write!(f, "{name:?}")
+        }
+    }
+}
+
+impl ResourceName {
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            ResourceName::Id(id) => Some(*id),
+            ResourceName::Name(_) => None
+        }
+    }
+}
+
+/// A level of the resource directory tree.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResourceDirectory {
+    pub characteristics: u32,
+    pub timestamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub entries: Vec<ResourceDirectoryEntry>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResourceDirectoryEntry {
+    pub name: ResourceName,
+    pub kind: ResourceDirectoryEntryKind
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ResourceDirectoryEntryKind {
+    Directory(ResourceDirectory),
+    Data(ResourceDataEntry)
+}
+
+/// A leaf of the resource directory tree.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResourceDataEntry {
+    /// The offset of the data in the section that holds it, or its RVA in an image.
+    pub offset: u32,
+    pub size: u32,
+    pub code_page: u32,
+    /// The resolved resource data.
+    pub data: Vec<u8>
+}
+
+/// The layout of the resource directory as nested maps of type, name and language, without the resource data.
+///
+/// Meant for printing or serializing the layout of a build, e.g. to spot changes in the output of the writers.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct ResourceTree {
+    pub types: BTreeMap<ResourceName, BTreeMap<ResourceName, BTreeMap<u16, ResourceTreeEntry>>>
+}
+
+/// A data entry of a [`ResourceTree`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ResourceTreeEntry {
+    /// The offset of the data in the section that holds it.
+    pub offset: u32,
+    pub size: u32,
+    pub code_page: u32
+}
+
+/// A single resource of the resource directory.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedResource {
+    pub ty: ResourceName,
+    pub name: ResourceName,
+    pub language: u16,
+    pub code_page: u32,
+    pub data: Vec<u8>
+}
+
+/// A single resource of a RES file, including all of its header fields.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedResEntry {
+    pub ty: ResourceName,
+    pub name: ResourceName,
+    pub data_version: u32,
+    pub memory_flags: u16,
+    pub language: u16,
+    pub version: u32,
+    pub characteristics: u32,
+    pub data: Vec<u8>
+}
+
+/// The resources of an object file, a `.lib` archive or a PE image, decoded as far as this crate understands them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedResourceSet {
+    /// All resources, including the ones that are decoded below.
+    pub resources: Vec<ParsedResource>,
+    /// The first version info resource.
+    pub version: Option<ParsedVersionInfo>,
+    pub icons: Vec<ParsedIconGroup>,
+    /// The first manifest resource.
+    pub manifest: Option<String>
+}
+
+/// A decoded `VS_VERSIONINFO` resource.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedVersionInfo {
+    pub language: u16,
+    pub file_version: Version,
+    pub product_version: Version,
+    pub flags_mask: u32,
+    pub flags: u32,
+    pub file_os: u32,
+    pub file_type: u32,
+    pub file_subtype: u32,
+    pub file_date: u64,
+    /// The strings of all string tables, later tables overwrite earlier ones.
+    pub strings: BTreeMap<String, String>
+}
+
+/// An icon group together with the data of its images.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsedIconGroup {
+    pub name: ResourceName,
+    pub language: u16,
+    /// The PNG or DIB data of every image in the group, which can be loaded with
+    /// [`Icon::try_from_png_bytes`](crate::Icon::try_from_png_bytes) or [`Icon::try_from_dib_bytes`](crate::Icon::try_from_dib_bytes).
+    pub images: Vec<Vec<u8>>
+}
+
+const FILE_HEADER_SIZE: usize = 20;
+const SYMBOL_SIZE: usize = 18;
+const SUBDIRECTORY_BIT: u32 = 1 << 31;
+const IMAGE_SYM_CLASS_STATIC: u8 = 0x03;
+
+/// Parses a COFF object file, like the ones produced by the COFF writer, and resolves its resource directory.
+pub fn parse_coff(bytes: &[u8]) -> Result<ParsedResources, ParseError> {
+    let mut r = Reader::new(bytes);
+    let machine = r.read_u16()?;
+    let number_of_sections = r.read_u16()?;
+    let timestamp = r.read_u32()?;
+    let symbol_table_pointer = r.read_u32()? as usize;
+    let number_of_symbols = r.read_u32()? as usize;
+    let optional_header_size = r.read_u16()? as usize;
+    let characteristics = r.read_u16()?;
+    r.set_pos(FILE_HEADER_SIZE + optional_header_size);
+
+    let string_table_pointer = symbol_table_pointer + number_of_symbols * SYMBOL_SIZE;
+    let string_table = match symbol_table_pointer {
+        0 => &[][..],
+        _ => {
+            let mut st = Reader::at(bytes, string_table_pointer);
+            let size = st.read_u32()? as usize;
+            st.set_pos(string_table_pointer);
+            st.read_bytes(size.max(4))?
+        }
+    };
+
+    let mut sections = Vec::with_capacity(number_of_sections as usize);
+    for _ in 0..number_of_sections {
+        let name = read_name(r.read_array()?, string_table)?;
+        let _physical_address = r.read_u32()?;
+        let _virtual_address = r.read_u32()?;
+        let size_of_raw_data = r.read_u32()? as usize;
+        let pointer_to_raw_data = r.read_u32()? as usize;
+        let pointer_to_relocations = r.read_u32()? as usize;
+        let _pointer_to_line_numbers = r.read_u32()?;
+        let number_of_relocations = r.read_u16()?;
+        let _number_of_line_numbers = r.read_u16()?;
+        let characteristics = r.read_u32()?;
+
+        let data = Reader::at(bytes, pointer_to_raw_data)
+            .read_bytes(size_of_raw_data)?
+            .to_vec();
+        let mut rr = Reader::at(bytes, pointer_to_relocations);
+        let relocations = (0..number_of_relocations)
+            .map(|_| {
+                Ok(ParsedRelocation {
+                    virtual_address: rr.read_u32()?,
+                    symbol_index: rr.read_u32()?,
+                    kind: rr.read_u16()?
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        sections.push(ParsedSection {
+            name,
+            characteristics,
+            data,
+            relocations
+        });
+    }
+
+    let mut symbols = Vec::new();
+    let mut sr = Reader::at(bytes, symbol_table_pointer);
+    let mut index = 0;
+    while index < number_of_symbols {
+        let name = read_name(sr.read_array()?, string_table)?;
+        let value = sr.read_u32()?;
+        let section_number = sr.read_u16()? as i16;
+        let ty = sr.read_u16()?;
+        let storage_class = sr.read_u8()?;
+        let number_of_aux_symbols = sr.read_u8()? as usize;
+        let aux_start = sr.pos();
+        let section_aux = match (storage_class, number_of_aux_symbols) {
+            (IMAGE_SYM_CLASS_STATIC, 1..) if value == 0 && name.starts_with('.') => Some(ParsedSectionAux {
+                length: sr.read_u32()?,
+                number_of_relocations: sr.read_u16()?,
+                number_of_line_numbers: sr.read_u16()?,
+                checksum: sr.read_u32()?,
+                number: sr.read_u16()?,
+                selection: sr.read_u8()?
+            }),
+            _ => None
+        };
+        sr.set_pos(aux_start + number_of_aux_symbols * SYMBOL_SIZE);
+        symbols.push(ParsedSymbol {
+            index: index as u32,
+            name,
+            value,
+            section_number,
+            ty,
+            storage_class,
+            section_aux
+        });
+        index += 1 + number_of_aux_symbols;
+    }
+
+    let directory = match sections
+        .iter()
+        .position(|s| s.name == ".rsrc$01" || s.name == ".rsrc")
+    {
+        Some(table_section) => {
+            let resolve = |offset, data_rva, size| resolve_relocated(&sections, &symbols, table_section, offset, data_rva, size);
+            Some(read_resource_tree(&sections[table_section].data, &resolve)?)
+        }
+        None => None
+    };
+
+    Ok(ParsedResources {
+        machine,
+        timestamp,
+        characteristics,
+        sections,
+        symbols,
+        directory
+    })
+}
+
+fn read_name(name: [u8; 8], string_table: &[u8]) -> Result<String, ParseError> {
+    let bytes = match (name[..4] == [0; 4], name[0] == b'/') {
+        // symbol names longer than 8 bytes: zeroes followed by an offset into the string table
+        (true, _) => {
+            let offset = u32::from_le_bytes(name[4..].try_into().unwrap()) as usize;
+            let tail = string_table
+                .get(offset..)
+                .ok_or(ParseError::Invalid("name points outside of the string table"))?;
+            &tail[..tail.iter().position(|&b| b == 0).unwrap_or(tail.len())]
+        }
+        // section names longer than 8 bytes: a slash followed by the decimal offset into the string table
+        (false, true) => {
+            let offset: usize = std::str::from_utf8(&name[1..])
+                .ok()
+                .and_then(|s| s.trim_end_matches('\0').parse().ok())
+                .ok_or(ParseError::Invalid("malformed long section name"))?;
+            let tail = string_table
+                .get(offset..)
+                .ok_or(ParseError::Invalid("name points outside of the string table"))?;
+            &tail[..tail.iter().position(|&b| b == 0).unwrap_or(tail.len())]
+        }
+        (false, false) => &name[..name.iter().position(|&b| b == 0).unwrap_or(8)]
+    };
+    String::from_utf8(bytes.to_vec()).map_err(|_| ParseError::Invalid("name is not valid UTF-8"))
+}
+
+/// Resolves the data of the data entry at the given offset of the table from its RVA and size,
+/// returning it together with its offset in the section that holds it.
+type DataResolver<'a> = dyn Fn(usize, u32, u32) -> Result<(u32, Vec<u8>), ParseError> + 'a;
+
+/// The levels of a resource tree: types, names and languages.
+const RESOURCE_TREE_DEPTH: usize = 3;
+
+/// Reads the resource tree whose root directory is at the start of the table.
+fn read_resource_tree(table: &[u8], resolve: &DataResolver) -> Result<ResourceDirectory, ParseError> {
+    read_directory(table, 0, 0, &mut BTreeSet::from([0]), resolve)
+}
+
+/// Reads the directory at the given offset, which is `depth` levels below the root.
+/// `visited` holds the offsets of all directories read so far, as every directory may only be referenced once.
+fn read_directory(
+    table: &[u8], offset: usize, depth: usize, visited: &mut BTreeSet<usize>, resolve: &DataResolver
+) -> Result<ResourceDirectory, ParseError> {
+    let mut r = Reader::at(table, offset);
+    let characteristics = r.read_u32()?;
+    let timestamp = r.read_u32()?;
+    let major_version = r.read_u16()?;
+    let minor_version = r.read_u16()?;
+    let number_of_entries = r.read_u16()? as usize + r.read_u16()? as usize;
+
+    let mut entries = Vec::with_capacity(number_of_entries);
+    for _ in 0..number_of_entries {
+        let name = r.read_u32()?;
+        let name = match name & SUBDIRECTORY_BIT {
+            0 => ResourceName::Id(name),
+            _ => {
+                let mut nr = Reader::at(table, (name & !SUBDIRECTORY_BIT) as usize);
+                let len = nr.read_u16()? as usize;
+                let chars = (0..len)
+                    .map(|_| nr.read_u16())
+                    .collect::<Result<Vec<_>, _>>()?;
+                ResourceName::Name(String::from_utf16(&chars).map_err(|_| ParseError::Invalid("resource name is not valid UTF-16"))?)
+            }
+        };
+        let target = r.read_u32()?;
+        let kind = match target & SUBDIRECTORY_BIT {
+            0 => ResourceDirectoryEntryKind::Data(read_data_entry(table, target as usize, resolve)?),
+            _ => {
+                let target = (target & !SUBDIRECTORY_BIT) as usize;
+                if depth + 1 >= RESOURCE_TREE_DEPTH {
+                    return Err(ParseError::Invalid("resource directory is nested deeper than type, name and language"));
+                }
+                if !visited.insert(target) {
+                    return Err(ParseError::Invalid("resource directory is referenced more than once"));
+                }
+                ResourceDirectoryEntryKind::Directory(read_directory(table, target, depth + 1, visited, resolve)?)
+            }
+        };
+        entries.push(ResourceDirectoryEntry { name, kind });
+    }
+
+    Ok(ResourceDirectory {
+        characteristics,
+        timestamp,
+        major_version,
+        minor_version,
+        entries
+    })
+}
+
+fn read_data_entry(table: &[u8], offset: usize, resolve: &DataResolver) -> Result<ResourceDataEntry, ParseError> {
+    let mut r = Reader::at(table, offset);
+    let data_rva = r.read_u32()?;
+    let size = r.read_u32()?;
+    let code_page = r.read_u32()?;
+    let _reserved = r.read_u32()?;
+    let (offset, data) = resolve(offset, data_rva, size)?;
+    Ok(ResourceDataEntry {
+        offset,
+        size,
+        code_page,
+        data
+    })
+}
+
+/// In an object file the RVA is not known yet, so the location of the data is given by a relocation.
+fn resolve_relocated(
+    sections: &[ParsedSection], symbols: &[ParsedSymbol], table_section: usize, offset: usize, data_rva: u32, size: u32
+) -> Result<(u32, Vec<u8>), ParseError> {
+    let relocation = sections[table_section]
+        .relocations
+        .iter()
+        .find(|rel| rel.virtual_address as usize == offset)
+        .ok_or(ParseError::Invalid("resource data entry without relocation"))?;
+    let symbol = symbols
+        .iter()
+        .find(|s| s.index == relocation.symbol_index)
+        .ok_or(ParseError::Invalid("relocation points to a non-existing symbol"))?;
+    let data_section = symbol_section(sections, symbol).ok_or(ParseError::Invalid("symbol points to a non-existing section"))?;
+    let start = symbol.value as usize + data_rva as usize;
+    let data = Reader::at(&data_section.data, start)
+        .read_bytes(size as usize)?
+        .to_vec();
+    Ok((start as u32, data))
+}
+
+/// The section of a symbol, if its one-based section number refers to one.
+/// Special section numbers like `IMAGE_SYM_UNDEFINED` (0) and negative ones don't.
+fn symbol_section<'a>(sections: &'a [ParsedSection], symbol: &ParsedSymbol) -> Option<&'a ParsedSection> {
+    usize::try_from(symbol.section_number)
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| sections.get(i))
+}
+
+/// Parses a RES file, like the ones produced by `rc.exe` or the RES writer.
+///
+/// The returned list also contains the empty resource at the start of the file.
+pub fn parse_res(bytes: &[u8]) -> Result<Vec<ParsedResEntry>, ParseError> {
+    let mut r = Reader::new(bytes);
+    let mut entries = Vec::new();
+    while r.pos() < bytes.len() {
+        let header_start = r.pos();
+        let data_size = r.read_u32()? as usize;
+        let header_size = r.read_u32()? as usize;
+        let ty = read_res_name(&mut r)?;
+        let name = read_res_name(&mut r)?;
+        r.align_to(4);
+        let data_version = r.read_u32()?;
+        let memory_flags = r.read_u16()?;
+        let language = r.read_u16()?;
+        let version = r.read_u32()?;
+        let characteristics = r.read_u32()?;
+        if r.pos() - header_start != header_size {
+            return Err(ParseError::Invalid("resource header size does not match its content"));
+        }
+        let data = r.read_bytes(data_size)?.to_vec();
+        r.align_to(4);
+        entries.push(ParsedResEntry {
+            ty,
+            name,
+            data_version,
+            memory_flags,
+            language,
+            version,
+            characteristics,
+            data
+        });
+    }
+    Ok(entries)
+}
+
+fn read_res_name(r: &mut Reader) -> Result<ResourceName, ParseError> {
+    let start = r.pos();
+    match r.read_u16()? {
+        0xffff => Ok(ResourceName::Id(r.read_u16()? as u32)),
+        _ => {
+            r.set_pos(start);
+            Ok(ResourceName::Name(r.read_utf16()?))
+        }
+    }
+}
+
+/// Reads the resources of a COFF object, a `.lib` archive of objects or a PE image (`.exe` or `.dll`),
+/// and decodes the version info, the icons and the manifest.
+///
+/// Archive members that are not COFF objects, like the short import headers of import libraries, are skipped.
+pub fn read_resources(bytes: &[u8]) -> Result<ParsedResourceSet, ParseError> {
+    const ARCHIVE_SIGNATURE: &[u8] = b"!<arch>\n";
+    // every object of an archive has its own resource directory, with their own icon ids
+    let units = if bytes.starts_with(b"MZ") {
+        vec![read_image_resources(bytes)?]
+    } else if let Some(members) = bytes.strip_prefix(ARCHIVE_SIGNATURE) {
+        let mut units = Vec::new();
+        for member in archive_members(members)? {
+            // import headers start with IMAGE_FILE_MACHINE_UNKNOWN followed by 0xFFFF
+            if member.starts_with(&[0x00, 0x00, 0xFF, 0xFF]) {
+                continue;
+            }
+            units.push(parse_coff(member)?.resources());
+        }
+        units
+    } else {
+        vec![parse_coff(bytes)?.resources()]
+    };
+
+    let is_type = |r: &ParsedResource, ty: ResourceType| r.ty == ResourceName::Id(ty.id() as u32);
+    let mut icons = Vec::new();
+    for unit in &units {
+        for group in unit.iter().filter(|r| is_type(r, ResourceType::IconGroup)) {
+            let images = read_icon_group(&group.data)?
+                .into_iter()
+                .map(|id| {
+                    let matching = |r: &&ParsedResource| is_type(r, ResourceType::Icon) && r.name == ResourceName::Id(id as u32);
+                    // images should have the language of their group, but don't insist on it
+                    unit.iter()
+                        .filter(matching)
+                        .find(|r| r.language == group.language)
+                        .or_else(|| unit.iter().find(matching))
+                        .map(|r| r.data.clone())
+                        .ok_or(ParseError::Invalid("icon group refers to a non-existing icon"))
+                })
+                .collect::<Result<_, _>>()?;
+            icons.push(ParsedIconGroup {
+                name: group.name.clone(),
+                language: group.language,
+                images
+            });
+        }
+    }
+
+    let resources: Vec<ParsedResource> = units.into_iter().flatten().collect();
+    let version = resources
+        .iter()
+        .find(|r| is_type(r, ResourceType::Version))
+        .map(|r| read_version_info(&r.data, r.language))
+        .transpose()?;
+    let manifest = resources
+        .iter()
+        .find(|r| is_type(r, ResourceType::Manifest))
+        .map(|r| String::from_utf8(r.data.clone()).map_err(|_| ParseError::Invalid("manifest is not valid UTF-8")))
+        .transpose()?;
+
+    Ok(ParsedResourceSet {
+        resources,
+        version,
+        icons,
+        manifest
+    })
+}
+
+/// Reads the first version info of a COFF object, a `.lib` archive or a PE image, see [`read_resources`].
+///
+/// This is meant for checks like comparing the embedded version with the version of the crate, e.g. in CI.
+/// Returns `None` if there is no version info.
+pub fn read_version(bytes: &[u8]) -> Result<Option<ParsedVersionInfo>, ParseError> {
+    Ok(read_resources(bytes)?.version)
+}
+
+/// Splits the body of an `ar` archive into the data of its members, skipping the linker and long name members.
+fn archive_members(bytes: &[u8]) -> Result<Vec<&[u8]>, ParseError> {
+    const MEMBER_HEADER_SIZE: usize = 60;
+    let mut r = Reader::new(bytes);
+    let mut members = Vec::new();
+    while r.pos() < bytes.len() {
+        let header = r.read_bytes(MEMBER_HEADER_SIZE)?;
+        if &header[58..] != b"`\n" {
+            return Err(ParseError::Invalid("malformed archive member header"));
+        }
+        let size: usize = std::str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|s| s.trim_end().parse().ok())
+            .ok_or(ParseError::Invalid("malformed archive member size"))?;
+        let data = r.read_bytes(size)?;
+        if !matches!(&header[..2], b"/ " | b"//") {
+            members.push(data);
+        }
+        r.align_to(2);
+    }
+    Ok(members)
+}
+
+/// Reads the resource directory of a PE image, whose data entries hold the final RVAs of the data.
+fn read_image_resources(bytes: &[u8]) -> Result<Vec<ParsedResource>, ParseError> {
+    const PE32_MAGIC: u16 = 0x10b;
+    const PE32_PLUS_MAGIC: u16 = 0x20b;
+    const RESOURCE_DIRECTORY_INDEX: u32 = 2;
+    const SECTION_HEADER_SIZE: usize = 40;
+
+    let pe_header = Reader::at(bytes, 0x3c).read_u32()? as usize;
+    let mut r = Reader::at(bytes, pe_header);
+    if r.read_array()? != *b"PE\0\0" {
+        return Err(ParseError::Invalid("missing PE signature"));
+    }
+    let _machine = r.read_u16()?;
+    let number_of_sections = r.read_u16()? as usize;
+    r.read_bytes(12)?; // TimeDateStamp, PointerToSymbolTable, NumberOfSymbols
+    let optional_header_size = r.read_u16()? as usize;
+    let _characteristics = r.read_u16()?;
+    let optional_header = r.pos();
+    let data_directories = match r.read_u16()? {
+        PE32_MAGIC => optional_header + 96,
+        PE32_PLUS_MAGIC => optional_header + 112,
+        _ => return Err(ParseError::Invalid("unknown optional header magic"))
+    };
+
+    let mut sections = Vec::with_capacity(number_of_sections);
+    let mut sr = Reader::at(bytes, optional_header + optional_header_size);
+    for _ in 0..number_of_sections {
+        let mut hr = Reader::new(sr.read_bytes(SECTION_HEADER_SIZE)?);
+        let _name = hr.read_bytes(8)?;
+        let virtual_size = hr.read_u32()?;
+        let virtual_address = hr.read_u32()?;
+        let size_of_raw_data = hr.read_u32()?;
+        let pointer_to_raw_data = hr.read_u32()? as usize;
+        let data = Reader::at(bytes, pointer_to_raw_data).read_bytes(size_of_raw_data as usize)?;
+        sections.push((virtual_address, virtual_size.max(size_of_raw_data), data));
+    }
+    // the part of the image at `rva`, as far as it is backed by the file
+    let at_rva = |rva: u32| {
+        sections
+            .iter()
+            .find(|&&(address, size, _)| rva >= address && rva - address < size)
+            .map(|&(address, _, data)| data.get((rva - address) as usize..).unwrap_or_default())
+            .ok_or(ParseError::Invalid("RVA points outside of the image"))
+    };
+
+    let number_of_directories = Reader::at(bytes, data_directories - 4).read_u32()?;
+    if number_of_directories <= RESOURCE_DIRECTORY_INDEX {
+        return Ok(Vec::new());
+    }
+    let mut dr = Reader::at(bytes, data_directories + 8 * RESOURCE_DIRECTORY_INDEX as usize);
+    let (resource_rva, resource_size) = (dr.read_u32()?, dr.read_u32()?);
+    if resource_rva == 0 || resource_size == 0 {
+        return Ok(Vec::new());
+    }
+    let table = at_rva(resource_rva)?;
+    let resolve = |_, data_rva, size| {
+        let data = Reader::new(at_rva(data_rva)?)
+            .read_bytes(size as usize)?
+            .to_vec();
+        Ok((data_rva, data))
+    };
+    Ok(flatten_directory(&read_resource_tree(table, &resolve)?))
+}
+
+/// Returns the ids of the images in a `GRPICONDIR`.
+fn read_icon_group(data: &[u8]) -> Result<Vec<u16>, ParseError> {
+    let mut r = Reader::new(data);
+    let _reserved = r.read_u16()?;
+    let _type = r.read_u16()?;
+    let count = r.read_u16()?;
+    (0..count)
+        .map(|_| {
+            r.read_bytes(12)?; // everything except the id, which is derived from the image anyway
+            r.read_u16()
+        })
+        .collect()
+}
+
+/// A field of the version info tree, see `VS_VERSIONINFO`.
+struct VersionField<'a> {
+    key: String,
+    /// The value, with the length in bytes for binary values and in characters for text values.
+    value: &'a [u8],
+    children: Vec<VersionField<'a>>
+}
+
+fn read_version_field(data: &[u8]) -> Result<VersionField<'_>, ParseError> {
+    let mut r = Reader::new(data);
+    let length = r.read_u16()? as usize;
+    let value_length = r.read_u16()? as usize;
+    let ty = r.read_u16()?;
+    let key = r.read_utf16()?;
+    r.align_to(4);
+    let data = data
+        .get(..length)
+        .ok_or(ParseError::UnexpectedEnd { offset: length })?;
+    let value_start = r.pos().min(length);
+    let value_bytes = match ty {
+        1 => 2 * value_length,
+        _ => value_length
+    };
+    let value = Reader::at(data, value_start).read_bytes(value_bytes)?;
+    r.set_pos(value_start + value_bytes);
+    r.align_to(4);
+    let mut children = Vec::new();
+    while r.pos() < length {
+        let child = read_version_field(&data[r.pos()..])?;
+        let child_length = Reader::at(data, r.pos()).read_u16()? as usize;
+        if child_length == 0 {
+            return Err(ParseError::Invalid("version info field with zero length"));
+        }
+        children.push(child);
+        r.set_pos(r.pos() + child_length);
+        r.align_to(4);
+    }
+    Ok(VersionField { key, value, children })
+}
+
+fn read_version_info(data: &[u8], language: u16) -> Result<ParsedVersionInfo, ParseError> {
+    const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF04BD;
+    let root = read_version_field(data)?;
+    if root.key != "VS_VERSION_INFO" {
+        return Err(ParseError::Invalid("version info does not start with VS_VERSION_INFO"));
+    }
+    let mut r = Reader::new(root.value);
+    if r.read_u32()? != FIXED_FILE_INFO_SIGNATURE {
+        return Err(ParseError::Invalid("missing VS_FIXEDFILEINFO signature"));
+    }
+    let _struct_version = r.read_u32()?;
+    let mut read_version = || -> Result<Version, ParseError> {
+        let (minor, major, build, patch) = (r.read_u16()?, r.read_u16()?, r.read_u16()?, r.read_u16()?);
+        Ok(Version::new(major, minor, patch, build))
+    };
+    let file_version = read_version()?;
+    let product_version = read_version()?;
+    let flags_mask = r.read_u32()?;
+    let flags = r.read_u32()?;
+    let file_os = r.read_u32()?;
+    let file_type = r.read_u32()?;
+    let file_subtype = r.read_u32()?;
+    let file_date = (r.read_u32()? as u64) << 32 | r.read_u32()? as u64;
+
+    let mut strings = BTreeMap::new();
+    for table in root
+        .children
+        .iter()
+        .filter(|c| c.key == "StringFileInfo")
+        .flat_map(|c| &c.children)
+    {
+        for string in &table.children {
+            let value = Reader::new(string.value)
+                .read_utf16()
+                .or_else(|err| match err {
+                    // the terminator is optional if the value fills its whole length
+                    ParseError::UnexpectedEnd { .. } => {
+                        let chars: Vec<u16> = string
+                            .value
+                            .chunks_exact(2)
+                            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                            .collect();
+                        String::from_utf16(&chars).map_err(|_| ParseError::Invalid("string is not valid UTF-16"))
+                    }
+                    err => Err(err)
+                })?;
+            strings.insert(string.key.clone(), value);
+        }
+    }
+
+    Ok(ParsedVersionInfo {
+        language,
+        file_version,
+        product_version,
+        flags_mask,
+        flags,
+        file_os,
+        file_type,
+        file_subtype,
+        file_date,
+        strings
+    })
+}
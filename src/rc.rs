@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use crate::{AccelFlags, LanguageId, ResourceBuilder, ResourceType, VersionInfo};
+
+/// Renders the resources of the builder as a resource script in the syntax of `rc.exe`.
+///
+/// This is meant for looking at what the builder produces, so binary data is only roughly approximated
+/// and files are referenced by the paths they were loaded from (or a placeholder name if there is none).
+pub(crate) fn write_rc_script(builder: &ResourceBuilder) -> String {
+    let mut rc = String::new();
+    write_script(builder, &mut rc).expect("Failed to format resource script");
+    rc
+}
+
+fn write_script(builder: &ResourceBuilder, rc: &mut String) -> std::fmt::Result {
+    writeln!(rc, "// Generated by embedinator for diagnostic purposes.")?;
+    writeln!(rc)?;
+    writeln!(rc, "LANGUAGE 0x09, 0x01 // English (United States)")?;
+
+    let translations = builder.version_translations();
+    if !builder.version.is_empty() {
+        writeln!(rc)?;
+        write_version(rc, LanguageId::LANG_US, &builder.version, &translations)?;
+    }
+    for (language, version) in &builder.localized_versions {
+        writeln!(rc)?;
+        write_language(rc, *language, |rc| write_version(rc, *language, version, &translations))?;
+    }
+
+    for (id, language, entries) in &builder.icon_groups {
+        writeln!(rc)?;
+        let icons: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| {
+                builder
+                    .icons
+                    .iter()
+                    .find(|(i, l, _)| *i == entry.icon_id && l == language)
+            })
+            .collect();
+        write_language(rc, *language, |rc| {
+            // rc.exe wants an .ico file, which we don't have, so the images are listed below
+            writeln!(rc, "{id} ICON \"icon_{id}.ico\"")?;
+            for (icon_id, _, icon) in icons {
+                write!(rc, "// icon {icon_id}: {}x{}, {} bytes", icon.width, icon.height, icon.data.len())?;
+                match &icon.source {
+                    Some(source) => writeln!(rc, ", {source}")?,
+                    None => writeln!(rc)?
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    for (id, dialog) in &builder.dialogs {
+        writeln!(rc)?;
+        writeln!(rc, "{id} DIALOGEX {}, {}, {}, {}", dialog.x, dialog.y, dialog.width, dialog.height)?;
+        writeln!(rc, "STYLE {:#x}", dialog.style)?;
+        if dialog.extended_style != 0 {
+            writeln!(rc, "EXSTYLE {:#x}", dialog.extended_style)?;
+        }
+        writeln!(rc, "CAPTION {}", quote(&dialog.title))?;
+        if let Some(font) = &dialog.font {
+            writeln!(rc, "FONT {}, {}", font.point_size, quote(&font.name))?;
+        }
+        writeln!(rc, "BEGIN")?;
+        for control in &dialog.controls {
+            writeln!(
+                rc,
+                "    CONTROL {}, {}, {}, {:#x}, {}, {}, {}, {}, {:#x}",
+                quote(&control.text),
+                control.id,
+                quote(control.class.name()),
+                control.style,
+                control.x,
+                control.y,
+                control.width,
+                control.height,
+                control.extended_style
+            )?;
+        }
+        writeln!(rc, "END")?;
+    }
+
+    for (id, accelerators) in &builder.accelerators {
+        writeln!(rc)?;
+        writeln!(rc, "{id} ACCELERATORS")?;
+        writeln!(rc, "BEGIN")?;
+        for accel in accelerators {
+            write!(rc, "    {}, {}", accel.key, accel.cmd)?;
+            let flags = [
+                (AccelFlags::VIRTKEY, "VIRTKEY"),
+                (AccelFlags::NOINVERT, "NOINVERT"),
+                (AccelFlags::SHIFT, "SHIFT"),
+                (AccelFlags::CONTROL, "CONTROL"),
+                (AccelFlags::ALT, "ALT")
+            ];
+            if !accel.flags.contains(AccelFlags::VIRTKEY) {
+                write!(rc, ", ASCII")?;
+            }
+            for (flag, name) in flags {
+                if accel.flags.contains(flag) {
+                    write!(rc, ", {name}")?;
+                }
+            }
+            writeln!(rc)?;
+        }
+        writeln!(rc, "END")?;
+    }
+
+    for (ty, id, data) in &builder.raw_resources {
+        writeln!(rc)?;
+        write_raw(rc, &id.to_string(), *ty, data)?;
+    }
+
+    for (ty, name, data) in &builder.named_resources {
+        writeln!(rc)?;
+        write_raw(rc, &quote(name), *ty, data)?;
+    }
+
+    for resource in &builder.imported {
+        writeln!(rc)?;
+        write_language(rc, resource.language, |rc| {
+            write_raw(rc, &resource.id.to_string(), resource.ty, &resource.data)
+        })?;
+    }
+
+    let manifests = builder
+        .manifest
+        .iter()
+        .map(|(language, manifest)| (builder.manifest_id(), *language, manifest))
+        .chain(
+            builder
+                .activation_manifests
+                .iter()
+                .map(|(id, manifest)| (*id, LanguageId::NEUTRAL, manifest))
+        );
+    for (id, language, manifest) in manifests {
+        writeln!(rc)?;
+        write_language(rc, language, |rc| {
+            writeln!(rc, "{id} {} // RT_MANIFEST", ResourceType::Manifest.id())?;
+            writeln!(rc, "BEGIN")?;
+            for line in String::from_utf8_lossy(manifest).lines() {
+                writeln!(rc, "    {}", quote(&format!("{line}\n")))?;
+            }
+            writeln!(rc, "END")
+        })?;
+    }
+    Ok(())
+}
+
+/// Writes a `VERSIONINFO` statement.
+fn write_version(rc: &mut String, language: LanguageId, version: &VersionInfo, translations: &[(LanguageId, u16)]) -> std::fmt::Result {
+    let codepage = version.effective_codepage();
+    let v = |v: crate::Version| format!("{}, {}, {}, {}", v.major, v.minor, v.patch, v.build);
+    writeln!(rc, "1 VERSIONINFO")?;
+    writeln!(rc, "FILEVERSION {}", v(version.file_version))?;
+    writeln!(rc, "PRODUCTVERSION {}", v(version.product_version))?;
+    writeln!(rc, "FILEFLAGSMASK {:#x}", version.file_flags_mask())?;
+    writeln!(rc, "FILEFLAGS {:#x}", version.flags.iter().fold(0, |acc, f| acc | *f as u32))?;
+    writeln!(rc, "FILEOS {:#x}", version.file_os as u32)?;
+    writeln!(rc, "FILETYPE {:#x}", version.file_type as u32)?;
+    writeln!(rc, "FILESUBTYPE {:#x}", version.file_subtype)?;
+    writeln!(rc, "BEGIN")?;
+    if !version.strings.is_empty() {
+        writeln!(rc, "    BLOCK \"StringFileInfo\"")?;
+        writeln!(rc, "    BEGIN")?;
+        writeln!(rc, "        BLOCK \"{:04x}{codepage:04x}\"", language.0)?;
+        writeln!(rc, "        BEGIN")?;
+        for (key, value) in &version.strings {
+            writeln!(rc, "            VALUE {}, {}", quote(key), quote(value))?;
+        }
+        writeln!(rc, "        END")?;
+        writeln!(rc, "    END")?;
+        writeln!(rc, "    BLOCK \"VarFileInfo\"")?;
+        writeln!(rc, "    BEGIN")?;
+        let others = translations.iter().filter(|(l, _)| *l != language);
+        let pairs: Vec<_> = std::iter::once(&(language, codepage))
+            .chain(others)
+            .map(|(language, codepage)| format!("{:#06x}, {codepage}", language.0))
+            .collect();
+        writeln!(rc, "        VALUE \"Translation\", {}", pairs.join(", "))?;
+        writeln!(rc, "    END")?;
+    }
+    writeln!(rc, "END")
+}
+
+/// Wraps the statements written by `f` in a `LANGUAGE` statement, unless they are in the default language anyway.
+fn write_language(rc: &mut String, language: LanguageId, f: impl FnOnce(&mut String) -> std::fmt::Result) -> std::fmt::Result {
+    if language == LanguageId::LANG_US {
+        return f(rc);
+    }
+    writeln!(rc, "LANGUAGE {:#04x}, {:#04x}", language.0 & 0x3ff, language.0 >> 10)?;
+    f(rc)?;
+    writeln!(rc, "LANGUAGE 0x09, 0x01")
+}
+
+/// Writes a user-defined resource with its data as a list of little-endian words.
+fn write_raw(rc: &mut String, name: &str, ty: ResourceType, data: &[u8]) -> std::fmt::Result {
+    // the type keywords only accept a file name, so even known types use their numeric id
+    match ty {
+        ResourceType::Html => writeln!(rc, "{name} {} // RT_HTML", ty.id())?,
+        ResourceType::Manifest => writeln!(rc, "{name} {} // RT_MANIFEST", ty.id())?,
+        ResourceType::AniCursor => writeln!(rc, "{name} {} // RT_ANICURSOR", ty.id())?,
+        ResourceType::TypeLib => writeln!(rc, "{name} TYPELIB")?,
+        ty => writeln!(rc, "{name} {}", ty.id())?
+    }
+    writeln!(rc, "BEGIN")?;
+    for line in data.chunks(16) {
+        let words: Vec<_> = line
+            .chunks(2)
+            .map(|word| match *word {
+                [lo, hi] => format!("{:#06x}", u16::from_le_bytes([lo, hi])),
+                // a single trailing byte can only be expressed as a narrow string
+                _ => format!("\"\\x{:02x}\"", word[0])
+            })
+            .collect();
+        writeln!(rc, "    {}", words.join(", "))?;
+    }
+    writeln!(rc, "END")
+}
+
+/// Quotes a string for use in a resource script.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\"\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
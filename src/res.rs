@@ -1,12 +1,40 @@
 use std::iter::repeat_n;
 
 use crate::binary::{BinaryWritable, BinaryWriter};
-use crate::ResourceType;
+use crate::coff::ResourceId;
+use crate::{LanguageId, ResourceType};
 
 #[derive(Default)]
 pub struct ResWriter(Vec<u8>);
 
+/// The fields of a resource header besides the type and the name.
+#[derive(Debug, Copy, Clone)]
+pub struct ResHeader {
+    pub data_version: u32,
+    pub flags: u16,
+    pub language: LanguageId,
+    pub version: u32,
+    pub characteristics: u32
+}
+
+impl ResHeader {
+    /// The header `rc.exe` writes for a resource of the type without any `VERSION` or `CHARACTERISTICS` statements.
+    pub fn new(ty: ResourceType, language: LanguageId) -> Self {
+        Self {
+            data_version: 0,
+            flags: ty.flags(),
+            language,
+            version: 0,
+            characteristics: 0
+        }
+    }
+}
+
 impl ResWriter {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
     pub fn finish(self) -> Vec<u8> {
         self.0
     }
@@ -30,25 +58,59 @@ impl ResWriter {
         self.write_u16(id);
     }
 
+    fn write_type(&mut self, ty: ResourceType) {
+        match ty.name() {
+            Some(name) => self.write_utf16(name),
+            None => self.write_ident(ty.id())
+        }
+    }
+
+    fn write_name(&mut self, id: &ResourceId) {
+        match id {
+            ResourceId::Name(name) => self.write_utf16(name),
+            ResourceId::Id(id) => self.write_ident(*id as u16)
+        }
+    }
+
     pub fn write_resource<B: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, name: u16, data: &B) {
+        let language = match ty {
+            ResourceType::None => LanguageId::NEUTRAL,
+            _ => LanguageId::LANG_US
+        };
+        self.write_resource_with_language(ty, name, language, data)
+    }
+
+    pub fn write_resource_with_language<B: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, name: u16, language: LanguageId, data: &B) {
+        self.write_resource_with(ty, name, ResHeader::new(ty, language), data)
+    }
+
+    /// Writes a resource that is identified by a string instead of an id. The name has to be uppercase already.
+    pub fn write_named_resource<B: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, name: &str, language: LanguageId, data: &B) {
+        self.write_entry(ty, &ResourceId::Name(name.to_string()), ResHeader::new(ty, language), data)
+    }
+
+    pub fn write_resource_with<B: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, name: u16, header: ResHeader, data: &B) {
+        self.write_entry(ty, &ResourceId::Id(name as u32), header, data)
+    }
+
+    fn write_entry<B: BinaryWritable + ?Sized>(&mut self, ty: ResourceType, name: &ResourceId, header: ResHeader, data: &B) {
         let header_start = self.pos();
+        debug_assert_eq!(header_start % 4, 0, "resource headers must start on a 4-byte boundary");
         let data_size_loc = self.reserve_u32();
         let header_size_loc = self.reserve_u32();
-        self.write_ident(ty as u16);
-        self.write_ident(name);
+        self.write_type(ty);
+        self.write_name(name);
         self.realign();
-        self.write_u32(0); // format version
-        self.write_u16(ty.flags());
-        self.write_u16(match ty {
-            ResourceType::None => 0x0,
-            _ => 0x0409 // en-US
-        });
-        self.write_u32(0); // data version
-        self.write_u32(0); // characteristics
+        self.write_u32(header.data_version);
+        self.write_u16(header.flags);
+        self.write_u16(header.language.0);
+        self.write_u32(header.version);
+        self.write_u32(header.characteristics);
 
         let header_len = self.pos() - header_start;
         self.update_u32(header_size_loc, header_len as u32);
         let data_start = self.pos();
+        debug_assert_eq!(data_start % 4, 0, "resource data must start on a 4-byte boundary");
         data.write_to(self);
         let data_len = self.pos() - data_start;
         self.update_u32(data_size_loc, data_len as u32);
@@ -0,0 +1,2240 @@
+//! Round-trip tests: builders are compiled in memory, parsed back with [`crate::parse`] and compared with what was added.
+
+use crate::parse::{parse_coff, ParsedResource, ParsedResources, ResourceName};
+use crate::{Icon, ResourceBuilder, ResourceType, TargetType};
+
+const TARGETS: [TargetType; 4] = [TargetType::Aarch64, TargetType::Arm64Ec, TargetType::I386, TargetType::X86_64];
+
+/// Compiles the builder for `target`, parses the object and checks the invariants the linker relies on.
+fn compile(builder: &ResourceBuilder, target: TargetType) -> (Vec<u8>, ParsedResources) {
+    let bytes = builder.compile(target);
+    let parsed = parse_coff(&bytes).expect("the object can't be parsed");
+    parsed.verify().expect("the object is inconsistent");
+    assert_eq!(parsed.target(), Some(target));
+    (bytes, parsed)
+}
+
+/// Compiles the builder for every target and returns the resources, which must not depend on the target.
+fn resources(builder: &ResourceBuilder) -> Vec<ParsedResource> {
+    let resources: Vec<_> = TARGETS
+        .iter()
+        .map(|target| compile(builder, *target).1.resources())
+        .collect();
+    assert!(
+        resources.windows(2).all(|pair| pair[0] == pair[1]),
+        "the resources differ between targets"
+    );
+    resources.into_iter().next().unwrap()
+}
+
+/// The type, name, language and size of every resource, in the order of the directory.
+fn layout(builder: &ResourceBuilder) -> Vec<(ResourceName, ResourceName, u16, usize)> {
+    resources(builder)
+        .into_iter()
+        .map(|r| (r.ty, r.name, r.language, r.data.len()))
+        .collect()
+}
+
+/// The resource with the type and id, which must exist exactly once.
+fn find(resources: &[ParsedResource], ty: ResourceType, id: u32) -> &ParsedResource {
+    let mut matching = resources
+        .iter()
+        .filter(|r| r.ty == ResourceName::Id(ty.id() as u32) && r.name == ResourceName::Id(id));
+    let resource = matching.next().expect("the resource is missing");
+    assert!(matching.next().is_none(), "the resource exists more than once");
+    resource
+}
+
+/// The header of a PNG with the given size and format. The image data is missing, which only matters for decoding.
+fn png(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+    let mut data = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    data.extend_from_slice(&13u32.to_be_bytes());
+    data.extend_from_slice(b"IHDR");
+    data.extend_from_slice(&width.to_be_bytes());
+    data.extend_from_slice(&height.to_be_bytes());
+    data.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+    data.extend_from_slice(&[0; 4]); // CRC, which is never checked
+    data
+}
+
+fn id(id: u32) -> ResourceName {
+    ResourceName::Id(id)
+}
+
+#[test]
+fn harness_checks_every_target() {
+    let builder = ResourceBuilder::default()
+        .set_file_version("1.2.3.4".parse().unwrap())
+        .add_string("ProductName", "harness")
+        .add_html(7, b"<p>".to_vec())
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_manifest("<assembly/>");
+    let layout = layout(&builder);
+    let types: Vec<_> = layout
+        .iter()
+        .map(|(ty, name, _, _)| (ty.clone(), name.clone()))
+        .collect();
+    assert_eq!(
+        types,
+        [(id(3), id(128)), (id(14), id(1)), (id(16), id(1)), (id(23), id(7)), (id(24), id(1))]
+    );
+    let resources = resources(&builder);
+    assert_eq!(find(&resources, ResourceType::Html, 7).data, b"<p>");
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data, png(16, 16, 8, 6));
+}
+
+#[test]
+fn harness_rejects_broken_relocations() {
+    let (mut bytes, _) = compile(&ResourceBuilder::default().add_html(1, vec![1, 2, 3]), TargetType::X86_64);
+    // turn the RVA relocation of the only data entry into an absolute one, using PointerToRelocations of the table section
+    let relocations = u32::from_le_bytes(bytes[20 + 24..20 + 28].try_into().unwrap()) as usize;
+    bytes[relocations + 8] = 0x01;
+    let parsed = parse_coff(&bytes).unwrap();
+    assert_eq!(parsed.sections[0].relocations[0].kind, 0x01);
+    assert!(parsed.verify().is_err());
+}
+
+/// Sets the section number of every symbol, skipping the auxiliary records.
+fn set_symbol_sections(bytes: &mut [u8], section_number: u16) {
+    let u32_at = |bytes: &[u8], i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+    let (table, count) = (u32_at(bytes, 8), u32_at(bytes, 12));
+    let mut index = 0;
+    while index < count {
+        let symbol = table + index * 18;
+        bytes[symbol + 12..symbol + 14].copy_from_slice(&section_number.to_le_bytes());
+        index += 1 + bytes[symbol + 17] as usize;
+    }
+}
+
+#[test]
+fn parser_rejects_out_of_range_section_numbers() {
+    let (bytes, _) = compile(&ResourceBuilder::default().add_html(1, vec![1, 2, 3]), TargetType::X86_64);
+    for section_number in [0x8000, 0xffff, 0, 3] {
+        let mut bytes = bytes.clone();
+        set_symbol_sections(&mut bytes, section_number);
+        assert!(parse_coff(&bytes).is_err());
+        assert!(crate::parse::read_resources(&bytes).is_err());
+    }
+}
+
+#[test]
+fn harness_rejects_out_of_range_section_numbers() {
+    let (_, parsed) = compile(&ResourceBuilder::default().add_html(1, vec![1, 2, 3]), TargetType::X86_64);
+    for section_number in [i16::MIN, -1, 0, 3] {
+        let mut parsed = parsed.clone();
+        for symbol in parsed
+            .symbols
+            .iter_mut()
+            .filter(|s| s.section_aux.is_some())
+        {
+            symbol.section_number = section_number;
+        }
+        assert!(parsed.verify().is_err());
+    }
+}
+
+/// A blank DIB as stored in `.ico` files, with a full palette for the low bit counts.
+fn dib(width: u32, height: u32, bit_count: u16) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&40u32.to_le_bytes());
+    data.extend_from_slice(&width.to_le_bytes());
+    data.extend_from_slice(&(height * 2).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&bit_count.to_le_bytes());
+    data.resize(40, 0);
+    if bit_count <= 8 {
+        data.resize(data.len() + (4 << bit_count), 0);
+    }
+    let stride = |bits: u32| (width * bits).div_ceil(32) * 4;
+    data.resize(data.len() + ((stride(bit_count as u32) + stride(1)) * height) as usize, 0);
+    data
+}
+
+#[test]
+fn icon_group_uses_the_bit_count_of_the_image() {
+    let resources = resources(&ResourceBuilder::default().add_icon(1, Icon::from_dib_bytes(dib(16, 16, 8))));
+    let group = &find(&resources, ResourceType::IconGroup, 1).data;
+    assert_eq!(group[6..12], [16, 16, 0, 0, 1, 0]);
+    assert_eq!(u16::from_le_bytes([group[12], group[13]]), 8);
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data, dib(16, 16, 8));
+}
+
+#[test]
+fn huge_dib_headers_are_rejected() {
+    let mut header = dib(16, 16, 32);
+    header[4..12].copy_from_slice(&[0xff; 8]);
+    assert!(matches!(Icon::try_from_dib_bytes(header), Err(crate::IconError::TooLarge { .. })));
+    let mut palette = dib(16, 16, 8);
+    palette[32..36].copy_from_slice(&[0xff; 4]);
+    assert_eq!(Icon::try_from_dib_bytes(palette).err(), Some(crate::IconError::InvalidDib));
+}
+
+/// A directory that is unique to this test run, for the APIs that work with paths.
+fn temp_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("embedinator-tests-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes a file to the [`temp_dir`].
+fn temp_file(name: &str, data: &[u8]) -> std::path::PathBuf {
+    let path = temp_dir().join(name);
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+/// An `.ico` file with the given PNG or DIB images. The directory entries only need the correct size and offset.
+fn ico(images: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = vec![0, 0, 1, 0];
+    data.extend_from_slice(&(images.len() as u16).to_le_bytes());
+    let mut offset = 6 + 16 * images.len();
+    for image in images {
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&(image.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += image.len();
+    }
+    for image in images {
+        data.extend_from_slice(image);
+    }
+    data
+}
+
+#[test]
+fn ico_files_are_read_from_disk() {
+    let images = [dib(16, 16, 8), png(48, 48, 8, 2)];
+    let icons = Icon::from_ico_file(temp_file("images.ico", &ico(&images))).unwrap();
+    let dimensions: Vec<_> = icons.iter().map(Icon::dimensions).collect();
+    assert_eq!(dimensions, [(16, 16), (48, 48)]);
+    let resources = resources(&ResourceBuilder::default().add_icon_group(1, icons));
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data, images[0]);
+    assert_eq!(find(&resources, ResourceType::Icon, 129).data, images[1]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn icons_round_trip_through_serde() {
+    let png_path = temp_file("serde.png", &png(32, 32, 8, 6));
+    let ico_path = temp_file("serde.ico", &ico(&[dib(16, 16, 8), png(48, 48, 8, 2)]));
+    let builder = ResourceBuilder::default()
+        .add_icon_path(1, &png_path)
+        .add_icon_ico_path(2, &ico_path);
+    let json = serde_json::to_string(&builder).unwrap();
+    let back: ResourceBuilder = serde_json::from_str(&json).unwrap();
+    assert!(back == builder);
+    assert_eq!(back.compile(TargetType::X86_64), builder.compile(TargetType::X86_64));
+
+    let dib_only = ResourceBuilder::default().add_icon(1, Icon::from_dib_bytes(dib(16, 16, 32)));
+    assert!(serde_json::to_string(&dib_only).is_err());
+}
+
+/// A real PNG that can be decoded, unlike the bare headers of [`png`].
+#[cfg(feature = "image")]
+fn encoded_png(size: u32) -> Vec<u8> {
+    let image = image::RgbaImage::from_pixel(size, size, image::Rgba([200, 100, 50, 255]));
+    Icon::from_dynamic_image(image.into()).data
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn bmp_fallback_adds_a_dib_in_front_of_the_png() {
+    let path = temp_file("fallback.png", &encoded_png(16));
+    let builder = ResourceBuilder::default()
+        .require_bmp_fallback(true)
+        .add_icon_path(1, &path);
+    let resources = resources(&builder);
+    let group = &find(&resources, ResourceType::IconGroup, 1).data;
+    // two entries, both 16x16 at 32bpp, with the DIB first
+    assert_eq!(group[4..6], [2, 0]);
+    assert_eq!(group[6..14], group[20..28]);
+    assert_eq!(u16::from_le_bytes([group[12], group[13]]), 32);
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data[..4], 40u32.to_le_bytes());
+    assert_eq!(find(&resources, ResourceType::Icon, 129).data, encoded_png(16));
+
+    // the fallback is not mistaken for the PNG it was made from
+    assert!(matches!(builder.icons[0].2.source, Some(crate::IconSource::BmpFallback { .. })));
+    assert!(builder.to_rc_script().contains("BMP fallback of"));
+    #[cfg(feature = "serde")]
+    {
+        let back: ResourceBuilder = serde_json::from_str(&serde_json::to_string(&builder).unwrap()).unwrap();
+        assert!(back == builder);
+        assert_eq!(back.compile(TargetType::X86_64), builder.compile(TargetType::X86_64));
+    }
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn corrupt_pngs_have_no_bmp_fallback() {
+    let icon = Icon::from_png_bytes(png(16, 16, 8, 6));
+    assert_eq!(icon.png_to_dib().err(), Some(crate::IconError::CorruptPng));
+}
+
+/// A RES entry with a named type, which only the `TYPELIB` type of the builder uses.
+fn res_entry_with_named_type(ty: &str, id: u16, data: &[u8]) -> Vec<u8> {
+    let mut names: Vec<u8> = ty
+        .encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    names.extend_from_slice(&[0xff, 0xff]);
+    names.extend_from_slice(&id.to_le_bytes());
+    names.resize(names.len().next_multiple_of(4), 0);
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&(8 + names.len() as u32 + 16).to_le_bytes());
+    entry.extend_from_slice(&names);
+    entry.extend_from_slice(&0u32.to_le_bytes());
+    entry.extend_from_slice(&0x30u16.to_le_bytes());
+    entry.extend_from_slice(&0x409u16.to_le_bytes());
+    entry.extend_from_slice(&[0; 8]);
+    entry.extend_from_slice(data);
+    entry.resize(entry.len().next_multiple_of(4), 0);
+    entry
+}
+
+#[test]
+fn res_files_with_named_resources_are_imported() {
+    let builder = ResourceBuilder::default()
+        .add_html(3, b"<p>".to_vec())
+        .add_file("index.html", ResourceType::Html, b"<html>".to_vec())
+        .add_file("app.js", ResourceType::Custom(256), b"main()".to_vec());
+    let res = builder.compile_to_res().data;
+    let imported = ResourceBuilder::default()
+        .try_add_res_file(res.clone())
+        .unwrap();
+    assert_eq!(resources(&imported), resources(&builder));
+    assert!(matches!(
+        imported.try_add_res_file(res),
+        Err(crate::EmbedError::DuplicateResource { .. } | crate::EmbedError::DuplicateNamedResource { .. })
+    ));
+
+    let typelib = res_entry_with_named_type("TYPELIB", 1, b"MSFT");
+    let imported = ResourceBuilder::default()
+        .try_add_res_file(typelib)
+        .unwrap();
+    assert_eq!(layout(&imported)[0].0, ResourceName::Name("TYPELIB".into()));
+    let custom = res_entry_with_named_type("MYTYPE", 1, b"data");
+    assert!(matches!(
+        ResourceBuilder::default().try_add_res_file(custom),
+        Err(crate::EmbedError::UnsupportedResource { .. })
+    ));
+    assert!(matches!(
+        ResourceBuilder::default().try_add_res_file(vec![1, 2, 3]),
+        Err(crate::EmbedError::InvalidResFile(_))
+    ));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn dynamic_libraries_are_detected_from_the_manifest() {
+    use crate::{manifest_library_kind, LibraryKind};
+    let multi_line = "[package]\nname = \"a\"\n\n[lib] # the plugin\ncrate-type = [\n    \"rlib\",\n    \"cdylib\",\n]\n";
+    assert_eq!(manifest_library_kind(multi_line, false), Some(LibraryKind::Cdylib));
+    assert_eq!(manifest_library_kind("[ lib ]\ncrate_type = ['dylib']", false), Some(LibraryKind::Dylib));
+    assert_eq!(
+        manifest_library_kind("lib = { crate-type = [\"cdylib\"] }", false),
+        Some(LibraryKind::Cdylib)
+    );
+    assert_eq!(manifest_library_kind("[lib]\ncrate-type = [\"cdylib\"]", true), None);
+    assert_eq!(
+        manifest_library_kind("[lib]\ncrate-type = [\"cdylib\"]\n\n[[bin]]\nname = \"tool\"", false),
+        None
+    );
+    assert_eq!(manifest_library_kind("[lib]\n# crate-type = [\"cdylib\"]", false), None);
+    assert_eq!(manifest_library_kind("[lib]\npath = \"src/cdylib.rs\"", false), None);
+}
+
+#[test]
+fn package_versions_fill_the_version_info() {
+    use crate::{EmbedError, FileFlag, Version};
+    let package = |version: &str| ResourceBuilder::from_package(version, "app", None);
+
+    let plain = package("1.2.3").unwrap();
+    assert_eq!(plain.version.file_version, Version::new(1, 2, 3, 0));
+    assert_eq!(plain.version.product_version, Version::new(1, 2, 3, 0));
+    assert!(plain.file_flags().is_empty());
+    assert_eq!(plain.version_strings()["FileVersion"], "1.2.3");
+
+    let prerelease = package("1.2.3-beta.1").unwrap();
+    assert_eq!(prerelease.version.file_version, Version::new(1, 2, 3, 0));
+    assert!(prerelease.file_flags().contains(&FileFlag::Prerelease));
+    assert_eq!(prerelease.version_strings()["ProductVersion"], "1.2.3-beta.1");
+
+    let build = package("1.2.3+45").unwrap();
+    assert_eq!(build.version.file_version, Version::new(1, 2, 3, 45));
+    assert!(build.file_flags().is_empty());
+    // build metadata that is not a plain number stays in the strings only
+    let both = package("1.2.3-rc.1+sha.5114f85").unwrap();
+    assert_eq!(both.version.file_version, Version::new(1, 2, 3, 0));
+    assert!(both.file_flags().contains(&FileFlag::Prerelease));
+    assert_eq!(both.version_strings()["FileVersion"], "1.2.3-rc.1+sha.5114f85");
+
+    assert!(matches!(package("1.0.70000"), Err(EmbedError::InvalidPackageVersion { .. })));
+    assert!(matches!(
+        package("1.0.0-70000.1"),
+        Ok(builder) if builder.version.file_version == Version::new(1, 0, 0, 0)
+    ));
+}
+
+#[test]
+fn source_date_becomes_the_file_date() {
+    let file_date = |builder: ResourceBuilder| {
+        let resources = resources(&builder.add_string("ProductName", "app"));
+        let data = &find(&resources, ResourceType::Version, 1).data;
+        let fixed = data
+            .windows(4)
+            .position(|w| w == 0xFEEF04BDu32.to_le_bytes())
+            .expect("the version info has no VS_FIXEDFILEINFO");
+        let dword = |offset: usize| u32::from_le_bytes(data[fixed + offset..fixed + offset + 4].try_into().unwrap());
+        (dword(44), dword(48))
+    };
+    // 2023-11-14 22:13:20 UTC is 0x01DA1747_C66D0000 in FILETIME
+    let date = ResourceBuilder::default().set_source_date(Some(1_700_000_000));
+    assert_eq!(date.version.file_date, 0x01DA_1747_C66D_0000);
+    assert_eq!(file_date(date), (0x01DA_1747, 0xC66D_0000));
+    assert_eq!(file_date(ResourceBuilder::default().set_source_date(Some(u64::MAX / 1000))), (0, 0));
+    assert_eq!(file_date(ResourceBuilder::default().set_source_date(None)), (0, 0));
+}
+
+#[test]
+fn version_only_embeds_a_single_resource() {
+    let mut version = crate::VersionInfo {
+        file_version: "2.0.0.1".parse().unwrap(),
+        ..Default::default()
+    };
+    version
+        .strings
+        .insert("FileVersion".into(), "2.0.0.1".into());
+    let builder = ResourceBuilder::version_only(version);
+    let layout = layout(&builder);
+    assert_eq!(layout.len(), 1);
+    assert_eq!((&layout[0].0, &layout[0].1, layout[0].2), (&id(16), &id(1), 0x409));
+    let (bytes, _) = compile(&builder, TargetType::X86_64);
+    let same = ResourceBuilder::default()
+        .set_file_version("2.0.0.1".parse().unwrap())
+        .add_string("FileVersion", "2.0.0.1");
+    assert_eq!(same.compile(TargetType::X86_64), bytes);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "resource headers must start on a 4-byte boundary")]
+fn misaligned_res_output_is_caught() {
+    use crate::binary::BinaryWriter;
+    let mut res = crate::res::ResWriter::default();
+    res.write_u16(0);
+    res.write_resource(ResourceType::Html, 1, &[1u8, 2, 3][..]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "version info must start on a 4-byte boundary")]
+fn misaligned_version_info_is_caught() {
+    use crate::binary::{BinaryWritable, BinaryWriter, VersionResource};
+    let mut res = crate::res::ResWriter::default();
+    res.write_u16(0);
+    VersionResource(crate::LanguageId::LANG_US, &Default::default(), &[]).write_to(&mut res);
+}
+
+#[test]
+fn identical_data_is_stored_once() {
+    let data = vec![7u8; 1000];
+    let once = ResourceBuilder::default().add_html(1, data.clone());
+    let twice = once.clone().add_html(2, data.clone());
+    let data_size = |builder: &ResourceBuilder| {
+        compile(builder, TargetType::X86_64).1.sections[1]
+            .data
+            .len()
+    };
+    assert_eq!(data_size(&twice), data_size(&once));
+
+    let tree = compile(&twice, TargetType::X86_64).1.tree();
+    let html = &tree.types[&id(23)];
+    assert_eq!(html[&id(1)][&0x409], html[&id(2)][&0x409]);
+    assert_eq!(find(&resources(&twice), ResourceType::Html, 2).data, data);
+
+    // the same image in two icon groups becomes a single RT_ICON that both groups refer to
+    let icon = Icon::from_png_bytes(png(32, 32, 8, 6));
+    let icons = ResourceBuilder::default()
+        .add_icon(1, icon.clone())
+        .add_icon(2, icon);
+    let icon_resources = resources(&icons);
+    assert_eq!(find(&icon_resources, ResourceType::Icon, 128).data, png(32, 32, 8, 6));
+    assert_eq!(
+        find(&icon_resources, ResourceType::IconGroup, 1).data,
+        find(&icon_resources, ResourceType::IconGroup, 2).data
+    );
+    assert_eq!(icon_resources.len(), 3);
+}
+
+#[test]
+fn long_symbol_names_go_into_the_string_table() {
+    // the second resource starts at 0x1000000, past the six hex digits that fit into an inline name
+    let builder = ResourceBuilder::default()
+        .add_raw_resource(ResourceType::Custom(300), 1, vec![1; 0x100_0000])
+        .add_raw_resource(ResourceType::Custom(300), 2, vec![2; 16]);
+    let (bytes, parsed) = compile(&builder, TargetType::X86_64);
+    let names: Vec<_> = parsed
+        .symbols
+        .iter()
+        .filter(|s| s.name.starts_with("$R"))
+        .map(|s| (s.name.as_str(), s.value))
+        .collect();
+    assert_eq!(names, [("$R000000", 0), ("$R1000000", 0x100_0000)]);
+
+    // the long name is referenced by its offset in the string table, which follows the symbols
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+    let (symbol_table, symbol_count) = (u32_at(8), u32_at(12));
+    let symbol = symbol_table + parsed.symbols.last().unwrap().index as usize * 18;
+    assert_eq!((u32_at(symbol), u32_at(symbol + 4)), (0, 4));
+    let string_table = &bytes[symbol_table + symbol_count * 18..];
+    assert_eq!(string_table, b"\x0e\0\0\0$R1000000\0");
+
+    for (_, object) in builder.compile_split(TargetType::X86_64) {
+        let parsed = parse_coff(&object).unwrap();
+        parsed.verify().unwrap();
+        let mut names: Vec<_> = parsed.symbols.iter().map(|s| &s.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), parsed.symbols.len());
+    }
+}
+
+#[test]
+fn output_does_not_depend_on_the_add_order() {
+    use crate::{AccelFlags, Accelerator, LanguageId, VersionInfo};
+    type Step = fn(ResourceBuilder) -> ResourceBuilder;
+    let steps: [Step; 10] = [
+        |b| b.add_string("ProductName", "order"),
+        |b| b.add_string("CompanyName", "nobody"),
+        |b| b.set_file_version("1.2.3.4".parse().unwrap()),
+        |b| b.add_html(2, b"two".to_vec()),
+        |b| b.add_html(1, b"one".to_vec()),
+        |b| b.add_file("b.js", ResourceType::Html, b"b".to_vec()),
+        |b| b.add_file("a.js", ResourceType::Html, b"a".to_vec()),
+        |b| b.add_icon_group_with_ids(5, [(200, Icon::from_png_bytes(png(16, 16, 8, 6)))]),
+        |b| b.add_icon_group_with_ids(4, [(201, Icon::from_png_bytes(png(32, 32, 8, 6)))]),
+        |b| {
+            b.add_manifest("<assembly/>")
+                .add_accelerators(3, vec![Accelerator::new(0x41, 1, AccelFlags::CONTROL)])
+                .add_version_for_language(LanguageId(0x407), VersionInfo::default())
+        }
+    ];
+    let forward = steps
+        .iter()
+        .fold(ResourceBuilder::default(), |b, step| step(b));
+    let backward = steps
+        .iter()
+        .rev()
+        .fold(ResourceBuilder::default(), |b, step| step(b));
+    for target in TARGETS {
+        assert_eq!(forward.compile(target), backward.compile(target));
+        assert_eq!(forward.compile_split(target), backward.compile_split(target));
+    }
+    assert_eq!(forward.compile_to_res().data, backward.compile_to_res().data);
+}
+
+#[test]
+fn cleared_icons_are_added_again_with_the_same_ids() {
+    let add_icons = |builder: ResourceBuilder| {
+        builder
+            .add_icon_group(1, [Icon::from_png_bytes(png(16, 16, 8, 6)), Icon::from_png_bytes(png(32, 32, 8, 6))])
+            .add_icon(2, Icon::from_png_bytes(png(48, 48, 8, 6)))
+    };
+    let base = ResourceBuilder::default().add_html(1, b"<p>".to_vec());
+    let fresh = add_icons(base.clone());
+    let mut cleared = add_icons(base.add_icon(3, Icon::from_png_bytes(png(24, 24, 8, 6))));
+    cleared.clear_icons();
+    assert_eq!(layout(&cleared), [(id(23), id(1), 0x409, 3)]);
+    let readded = add_icons(cleared);
+    assert_eq!(readded.compile(TargetType::X86_64), fresh.compile(TargetType::X86_64));
+}
+
+#[test]
+fn dangling_icon_group_entries_are_detected() {
+    let mut builder =
+        ResourceBuilder::default().add_icon_group(1, [Icon::from_png_bytes(png(16, 16, 8, 6)), Icon::from_png_bytes(png(32, 32, 8, 6))]);
+    assert!(builder.try_compile(TargetType::X86_64).is_ok());
+    builder.icons.retain(|(id, _, _)| *id != 129);
+    assert!(matches!(
+        builder.try_compile(TargetType::X86_64),
+        Err(crate::EmbedError::MissingIconImage { group: 1, image: 129, .. })
+    ));
+    assert!(builder.try_compile_split(TargetType::X86_64).is_err());
+}
+
+/// The `wLength`, `wValueLength` and value bytes of the `String` structure of a version info with the given key.
+fn version_string(data: &[u8], key: &str) -> (u16, u16, Vec<u8>) {
+    let key: Vec<u8> = key
+        .encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let key_start = data
+        .windows(key.len())
+        .position(|w| w == key.as_slice())
+        .expect("the key is missing");
+    let start = key_start - 6;
+    let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    let (length, value_length) = (u16_at(start), u16_at(start + 2));
+    assert_eq!(u16_at(start + 4), 1, "not a text value");
+    let value_start = (key_start + key.len()).next_multiple_of(4);
+    (length, value_length, data[value_start..start + length as usize].to_vec())
+}
+
+#[test]
+fn version_strings_encode_surrogate_pairs() {
+    let copyright = "© 2024 Crab Corp™ 🦀";
+    let builder = ResourceBuilder::default().add_string("LegalCopyright", copyright);
+    let resources = resources(&builder);
+    let (_, _, value) = version_string(&find(&resources, ResourceType::Version, 1).data, "LegalCopyright");
+    // ©, ™ and a null terminator in UTF-16, with the crab as the surrogate pair D83E DD80
+    assert_eq!(value[..2], [0xa9, 0x00]);
+    assert_eq!(value[value.len() - 8..], [0x20, 0x00, 0x3e, 0xd8, 0x80, 0xdd, 0x00, 0x00]);
+    assert!(value.windows(2).any(|w| w == [0x22, 0x21]));
+
+    let compiled = compile(&builder, TargetType::X86_64).0;
+    let version = crate::parse::read_version(&compiled).unwrap().unwrap();
+    assert_eq!(version.strings["LegalCopyright"], copyright);
+}
+
+#[test]
+fn version_string_lengths_count_code_units() {
+    let comment = "𝄞 clef, 🦀 crab, 𐐷 deseret";
+    let builder = ResourceBuilder::default()
+        .add_string("Comments", comment)
+        .add_string("ProductName", "after");
+    let resources = resources(&builder);
+    let data = &find(&resources, ResourceType::Version, 1).data;
+    let (length, value_length, value) = version_string(data, "Comments");
+    let units = comment.encode_utf16().count() + 1;
+    assert_ne!(units, comment.chars().count() + 1);
+    assert_eq!(value_length as usize, units);
+    assert_eq!(value.len(), units * 2);
+    // wLength covers the header, the padded key and the value, so the next string starts right behind it
+    let utf16 = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let header_start = data
+        .windows(16)
+        .position(|w| w == utf16("Comments"))
+        .unwrap()
+        - 6;
+    let next = (header_start + length as usize).next_multiple_of(4);
+    assert!(data[next + 6..].starts_with(&utf16("ProductName\0")));
+    assert_eq!(version_string(data, "ProductName").2, utf16("after\0"));
+}
+
+#[test]
+fn c_source_contains_the_object() {
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "c")
+        .add_html(1, vec![0xff; 33]);
+    let path = temp_file("resources.c", b"");
+    builder.write_c_source(TargetType::X86_64, &path).unwrap();
+    let source = std::fs::read_to_string(&path).unwrap();
+    let bytes: Vec<u8> = source
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter_map(|token| token.strip_prefix("0x"))
+        .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+        .collect();
+    let object = builder.compile(TargetType::X86_64);
+    assert_eq!(bytes, object);
+    assert!(source.contains(&format!("embedinator_resources_len = {};", object.len())));
+
+    // compile the file if there is a C compiler around, CI images without one still run the checks above
+    match std::process::Command::new("cc")
+        .args(["-std=c99", "-Wall", "-Werror", "-c", "-o"])
+        .arg(path.with_extension("o"))
+        .arg(&path)
+        .status()
+    {
+        Ok(status) => assert!(status.success(), "the generated C does not compile"),
+        Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound)
+    }
+}
+
+#[test]
+fn only_the_last_accelerator_is_marked() {
+    use crate::{AccelFlags, Accelerator};
+    let entries = vec![
+        Accelerator::new(0x4e, 100, AccelFlags::VIRTKEY | AccelFlags::CONTROL),
+        Accelerator::new(0x53, 101, AccelFlags::VIRTKEY | AccelFlags::CONTROL | AccelFlags::SHIFT),
+        Accelerator::new(0x73, 102, AccelFlags::VIRTKEY | AccelFlags::ALT),
+    ];
+    let builder = ResourceBuilder::default()
+        .add_accelerators(1, entries)
+        .add_accelerators(2, vec![Accelerator::new(0x41, 7, AccelFlags::NONE)]);
+    let resources = resources(&builder);
+    let table = &find(&resources, ResourceType::Accelerator, 1).data;
+    let records: Vec<[u16; 4]> = table
+        .chunks(8)
+        .map(|r| std::array::from_fn(|i| u16::from_le_bytes([r[2 * i], r[2 * i + 1]])))
+        .collect();
+    assert_eq!(records, [[0x09, 0x4e, 100, 0], [0x0d, 0x53, 101, 0], [0x91, 0x73, 102, 0]]);
+    assert_eq!(find(&resources, ResourceType::Accelerator, 2).data, [0x80, 0, 0x41, 0, 7, 0, 0, 0]);
+}
+
+#[test]
+fn html_is_stored_verbatim() {
+    // a UTF-8 BOM, Latin-1 bytes and a length that is not a multiple of the data alignment
+    let mut page = vec![0xef, 0xbb, 0xbf];
+    page.extend_from_slice(b"<html><body>caf\xe9</body></html>\r\n");
+    let builder = ResourceBuilder::default()
+        .add_html(1, page.clone())
+        .add_html(2, Vec::new());
+    let resources = resources(&builder);
+    assert_eq!(find(&resources, ResourceType::Html, 1).data, page);
+    assert!(find(&resources, ResourceType::Html, 2).data.is_empty());
+
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    let html: Vec<_> = res
+        .iter()
+        .filter(|entry| entry.ty == id(23))
+        .map(|entry| &entry.data)
+        .collect();
+    assert_eq!(html, [&page, &Vec::new()]);
+}
+
+#[test]
+fn res_files_hold_the_same_resources_as_objects() {
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "res")
+        .add_icon_group(1, [Icon::from_png_bytes(png(16, 16, 8, 6)), Icon::from_dib_bytes(dib(32, 32, 4))])
+        .add_html(5, b"<p>".to_vec())
+        .add_file("app.js", ResourceType::Html, b"run()".to_vec())
+        .add_manifest("<assembly/>");
+    let objects = resources(&builder);
+    let file = builder.clone().build_res();
+    assert_eq!(file.kind, crate::ResourceFileKind::Res);
+    let entries = crate::parse::parse_res(file.as_bytes()).unwrap();
+
+    // the leading empty resource is only part of RES files
+    let (empty, entries) = entries.split_first().unwrap();
+    assert_eq!((&empty.ty, &empty.name, empty.data.len()), (&id(0), &id(0), 0));
+    assert_eq!(entries.len(), objects.len());
+    for object in &objects {
+        let entry = entries
+            .iter()
+            .find(|e| e.ty == object.ty && e.name == object.name && e.language == object.language)
+            .expect("the resource is missing from the RES file");
+        assert_eq!(entry.data, object.data);
+    }
+}
+
+#[test]
+fn version_info_without_strings_has_no_child_blocks() {
+    let utf16 = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let contains = |data: &[u8], key: &str| data.windows(key.len() * 2).any(|w| w == utf16(key));
+
+    let builder = ResourceBuilder::default().set_file_version("1.0.0.0".parse().unwrap());
+    let without_strings = resources(&builder);
+    let data = &find(&without_strings, ResourceType::Version, 1).data;
+    // the root header, its padded key and the 52-byte VS_FIXEDFILEINFO, which is the whole resource
+    assert_eq!(data.len(), 6 + 32 + 2 + 52);
+    assert_eq!(u16::from_le_bytes([data[0], data[1]]) as usize, data.len());
+    assert!(!contains(data, "StringFileInfo"));
+    assert!(!contains(data, "VarFileInfo"));
+    let version = crate::parse::read_version(&compile(&builder, TargetType::X86_64).0)
+        .unwrap()
+        .unwrap();
+    assert!(version.strings.is_empty());
+    assert_eq!(version.file_version, "1.0.0.0".parse().unwrap());
+
+    let with_strings = resources(&builder.add_string("ProductName", "x"));
+    let data = &find(&with_strings, ResourceType::Version, 1).data;
+    assert!(contains(data, "StringFileInfo") && contains(data, "VarFileInfo"));
+}
+
+#[test]
+fn overlong_version_strings_are_reported() {
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "fine")
+        .add_string("Comments", "x".repeat(70_000));
+    let is_too_long =
+        |result: Result<_, crate::EmbedError>| matches!(result, Err(crate::EmbedError::VersionStringTooLong { key }) if key == "Comments");
+    assert!(is_too_long(builder.try_compile(TargetType::X86_64).map(drop)));
+    assert!(is_too_long(builder.try_compile_split(TargetType::X86_64).map(drop)));
+    assert!(is_too_long(builder.clone().try_build_res().map(drop)));
+    let c_source = builder.write_c_source(TargetType::X86_64, temp_dir().join("overlong.c"));
+    assert_eq!(c_source.map_err(|err| err.kind()), Err(std::io::ErrorKind::InvalidInput));
+    assert!(is_too_long(builder.compiled_size(TargetType::X86_64).map(drop)));
+    assert!(is_too_long(builder.content_fingerprint(TargetType::X86_64).map(drop)));
+
+    // the limit is on all strings together, so two halves that fit on their own fail as well
+    let halves = ResourceBuilder::default()
+        .add_string("Comments", "x".repeat(17_000))
+        .add_string("FileDescription", "x".repeat(17_000));
+    assert!(halves.try_compile(TargetType::X86_64).is_err());
+    let fits = ResourceBuilder::default().add_string("Comments", "x".repeat(32_000));
+    let version = crate::parse::read_version(&compile(&fits, TargetType::X86_64).0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(version.strings["Comments"].len(), 32_000);
+}
+
+#[test]
+fn resource_sections_are_read_only() {
+    let builder = ResourceBuilder::default()
+        .add_html(1, vec![1, 2, 3])
+        .add_manifest("<assembly/>");
+    for split in [false, true] {
+        let objects = match split {
+            false => vec![builder.compile(TargetType::X86_64)],
+            true => builder
+                .compile_split(TargetType::X86_64)
+                .into_iter()
+                .map(|(_, object)| object)
+                .collect()
+        };
+        for object in objects {
+            let parsed = parse_coff(&object).unwrap();
+            assert!(!parsed.sections.is_empty());
+            for section in &parsed.sections {
+                // IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ, without IMAGE_SCN_MEM_WRITE
+                assert_eq!(section.characteristics, 0x4000_0040, "{}", section.name);
+            }
+        }
+    }
+}
+
+#[test]
+fn windows_triples_are_parsed() {
+    let triples = [
+        ("x86_64-pc-windows-msvc", TargetType::X86_64),
+        ("x86_64-pc-windows-gnu", TargetType::X86_64),
+        ("i686-pc-windows-msvc", TargetType::I386),
+        ("i686-pc-windows-gnu", TargetType::I386),
+        ("i586-pc-windows-msvc", TargetType::I386),
+        ("aarch64-pc-windows-msvc", TargetType::Aarch64),
+        ("arm64ec-pc-windows-msvc", TargetType::Arm64Ec),
+        ("x86_64-uwp-windows-gnu", TargetType::X86_64)
+    ];
+    for (triple, target) in triples {
+        assert_eq!(triple.parse::<TargetType>(), Ok(target), "{triple}");
+    }
+    assert_eq!(
+        TargetType::from_triple("x86_64-unknown-linux-gnu"),
+        Err(crate::ParseTargetError::NotWindows("x86_64-unknown-linux-gnu".to_string()))
+    );
+    assert_eq!(
+        TargetType::from_triple("thumbv7a-pc-windows-msvc"),
+        Err(crate::ParseTargetError::UnsupportedArch("thumbv7a".to_string()))
+    );
+}
+
+#[test]
+fn arm64ec_objects_use_the_arm64_relocations() {
+    let (bytes, parsed) = compile(&ResourceBuilder::default().add_html(1, vec![1, 2, 3]), TargetType::Arm64Ec);
+    assert_eq!(bytes[0..2], 0xa641u16.to_le_bytes());
+    let relocations: Vec<_> = parsed
+        .sections
+        .iter()
+        .flat_map(|s| &s.relocations)
+        .collect();
+    assert!(!relocations.is_empty());
+    // IMAGE_REL_ARM64_ADDR32NB
+    assert!(relocations.iter().all(|r| r.kind == 0x0002));
+}
+
+#[test]
+fn rc_script_lists_the_resources() {
+    let builder = ResourceBuilder::default()
+        .set_file_version("1.2.3.4".parse().unwrap())
+        .add_string("ProductName", "Demo \"App\"")
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_manifest("<assembly/>")
+        .add_html(7, b"<p>".to_vec());
+    let expected = r#"// Generated by embedinator for diagnostic purposes.
+
+LANGUAGE 0x09, 0x01 // English (United States)
+
+1 VERSIONINFO
+FILEVERSION 1, 2, 3, 4
+PRODUCTVERSION 0, 0, 0, 0
+FILEFLAGSMASK 0x3f
+FILEFLAGS 0x0
+FILEOS 0x40004
+FILETYPE 0x1
+FILESUBTYPE 0x0
+BEGIN
+    BLOCK "StringFileInfo"
+    BEGIN
+        BLOCK "040904b0"
+        BEGIN
+            VALUE "ProductName", "Demo ""App"""
+        END
+    END
+    BLOCK "VarFileInfo"
+    BEGIN
+        VALUE "Translation", 0x0409, 1200
+    END
+END
+
+LANGUAGE 0x00, 0x00
+1 ICON "icon_1.ico"
+// icon 128: 16x16, 33 bytes
+LANGUAGE 0x09, 0x01
+
+7 23 // RT_HTML
+BEGIN
+    0x703c, "\x3e"
+END
+
+LANGUAGE 0x00, 0x00
+1 24 // RT_MANIFEST
+BEGIN
+    "<assembly/>\n"
+END
+LANGUAGE 0x09, 0x01
+"#;
+    assert_eq!(builder.to_rc_script(), expected);
+}
+
+#[test]
+fn section_symbols_carry_the_checksum_of_the_data() {
+    // bitwise CRC-32 without the final inversion, independent of the table used by the writer
+    let crc_from = |init: u32, data: &[u8]| {
+        data.iter().fold(init, |crc, &byte| {
+            (0..8).fold(crc ^ byte as u32, |crc, _| (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg()))
+        })
+    };
+    // with the usual initial value and final inversion this is the standard CRC-32
+    assert_eq!(!crc_from(!0, b"123456789"), 0xCBF43926);
+    let crc = |data: &[u8]| crc_from(0, data);
+    let builder = ResourceBuilder::default()
+        .add_html(1, b"<p>checksum</p>".to_vec())
+        .add_manifest("<assembly/>");
+    let (_, parsed) = compile(&builder, TargetType::X86_64);
+    let sections: Vec<_> = parsed
+        .symbols
+        .iter()
+        .filter_map(|s| Some((s.section_number, s.section_aux.as_ref()?)))
+        .collect();
+    assert_eq!(sections.len(), parsed.sections.len());
+    for (number, aux) in sections {
+        let section = &parsed.sections[number as usize - 1];
+        assert_ne!(aux.checksum, 0);
+        assert_eq!(aux.checksum, crc(&section.data), "{}", section.name);
+        assert_eq!((aux.number, aux.selection), (0, 0));
+    }
+}
+
+#[test]
+fn accessors_reflect_the_added_resources() {
+    let builder = ResourceBuilder::default();
+    assert_eq!(builder.icon_ids().count(), 0);
+    assert!(!builder.has_manifest());
+    assert!(builder.version_strings().is_empty());
+    assert!(builder.file_flags().is_empty());
+    let builder = builder
+        .add_icon(32512, Icon::from_png_bytes(png(32, 32, 8, 6)))
+        .add_icon(2, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_manifest("<assembly/>")
+        .add_string("ProductName", "accessors")
+        .add_file_flag(crate::FileFlag::Debug);
+    assert_eq!(builder.icon_ids().collect::<Vec<_>>(), [2, 32512]);
+    assert!(builder.has_manifest());
+    assert_eq!(builder.version_strings()["ProductName"], "accessors");
+    assert_eq!(builder.file_flags().iter().collect::<Vec<_>>(), [&crate::FileFlag::Debug]);
+}
+
+#[test]
+fn unsupported_pngs_are_rejected() {
+    use crate::IconError;
+    let mut interlaced = png(16, 16, 8, 6);
+    interlaced[28] = 1;
+    assert_eq!(Icon::try_from_png_bytes(interlaced).err(), Some(IconError::Interlaced));
+    for (bit_depth, color_type) in [(16, 6), (8, 3), (4, 3), (8, 0), (8, 4), (16, 2)] {
+        let err = Icon::try_from_png_bytes(png(16, 16, bit_depth, color_type)).err();
+        assert_eq!(err, Some(IconError::UnsupportedFormat { color_type, bit_depth }));
+    }
+    let message = IconError::UnsupportedFormat { color_type: 3, bit_depth: 4 }.to_string();
+    assert!(message.contains("4-bit paletted data (color type 3)"), "{message}");
+    assert_eq!(Icon::try_from_png_bytes(b"GIF89a".to_vec()).err(), Some(IconError::InvalidPng));
+    assert_eq!(
+        Icon::try_from_png_bytes(png(16, 16, 8, 6)[..20].to_vec()).err(),
+        Some(IconError::InvalidPngHeader)
+    );
+}
+
+#[test]
+fn chosen_icon_ids_do_not_depend_on_the_add_order() {
+    let small = || Icon::from_png_bytes(png(16, 16, 8, 6));
+    let large = || Icon::from_png_bytes(png(32, 32, 8, 6));
+    let first = ResourceBuilder::default()
+        .add_icon_group_with_ids(1, [(300, small()), (301, large())])
+        .add_icon_group_with_ids(2, [(301, large())]);
+    let second = ResourceBuilder::default()
+        .add_icon_group_with_ids(2, [(301, large())])
+        .add_icon_group_with_ids(1, [(300, small()), (301, large())]);
+    for builder in [&first, &second] {
+        let resources = resources(builder);
+        assert_eq!(find(&resources, ResourceType::Icon, 300).data, png(16, 16, 8, 6));
+        assert_eq!(find(&resources, ResourceType::Icon, 301).data, png(32, 32, 8, 6));
+    }
+    assert_eq!(first.compile(TargetType::X86_64), second.compile(TargetType::X86_64));
+    // automatic ids are the lowest free ones in the order of the calls
+    let automatic = ResourceBuilder::default()
+        .add_icon_group(1, [small(), large()])
+        .add_icon(2, large());
+    assert_eq!(
+        automatic
+            .icons
+            .iter()
+            .map(|(id, _, _)| *id)
+            .collect::<Vec<_>>(),
+        [128, 129]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Duplicate icon image id 300")]
+fn chosen_icon_ids_are_not_shared_by_different_images() {
+    let _ = ResourceBuilder::default()
+        .add_icon_group_with_ids(1, [(300, Icon::from_png_bytes(png(16, 16, 8, 6)))])
+        .add_icon_group_with_ids(2, [(300, Icon::from_png_bytes(png(32, 32, 8, 6)))]);
+}
+
+#[test]
+fn file_types_are_written_to_the_version_info() {
+    use crate::FileType;
+    let types = [
+        (FileType::Unknown, 0),
+        (FileType::Exe, 1),
+        (FileType::Dll, 2),
+        (FileType::Driver, 3),
+        (FileType::Font, 4),
+        (FileType::Vxd, 5),
+        (FileType::StaticLib, 7)
+    ];
+    for (file_type, id) in types {
+        // VFT2_DRV_SYSTEM and VFT2_FONT_TRUETYPE
+        let subtype = match file_type {
+            FileType::Driver => 7,
+            FileType::Font => 3,
+            _ => 0
+        };
+        let builder = ResourceBuilder::default()
+            .set_file_version("1.0.0.0".parse().unwrap())
+            .set_file_type(file_type)
+            .set_file_subtype(subtype);
+        let version = crate::parse::read_version(&compile(&builder, TargetType::X86_64).0)
+            .unwrap()
+            .unwrap();
+        assert_eq!((version.file_type, version.file_subtype), (id, subtype), "{file_type:?}");
+        assert_eq!(version.file_os, 0x40004);
+    }
+}
+
+/// Serializes the tests that set the environment variables of a build script.
+static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Runs `f` with the environment of a build script for `x86_64-pc-windows-{target_env}`, with `OUT_DIR` set to `out_dir`.
+fn with_build_env<T>(out_dir: &std::path::Path, target_env: &str, f: impl FnOnce() -> T) -> T {
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let vars = [
+        ("OUT_DIR", out_dir.as_os_str()),
+        ("CARGO_CFG_TARGET_ARCH", "x86_64".as_ref()),
+        ("CARGO_CFG_TARGET_ENV", target_env.as_ref())
+    ];
+    for (key, value) in vars {
+        std::env::set_var(key, value);
+    }
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    for (key, _) in vars {
+        std::env::remove_var(key);
+    }
+    result.unwrap_or_else(|err| std::panic::resume_unwind(err))
+}
+
+#[test]
+fn compile_matches_the_written_object() {
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "compile")
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)));
+    let expected = builder.compile(TargetType::X86_64);
+    let out_dir = temp_dir().join("compile");
+    let written = with_build_env(&out_dir, "msvc", || builder.clone().finish_returning());
+    assert_eq!(written.len(), 1);
+    assert_eq!(written[0].0, out_dir.join("resources.lib"));
+    assert_eq!(std::fs::read(&written[0].0).unwrap(), expected);
+    let path = out_dir.join("nested").join("resources.obj");
+    builder.finish_to(&path, TargetType::X86_64).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+}
+
+#[test]
+fn res_files_use_the_memory_flags_of_rc() {
+    use crate::{AccelFlags, Accelerator};
+    const MOVEABLE: u16 = 0x0010;
+    const PURE: u16 = 0x0020;
+    const DISCARDABLE: u16 = 0x1000;
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "flags")
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_dialog(1, crate::DialogTemplate::default())
+        .add_accelerators(1, vec![Accelerator::new(0x41, 1, AccelFlags::CONTROL)])
+        .add_html(1, b"<p>".to_vec())
+        .add_manifest("<assembly/>")
+        .add_ani_cursor(1, b"RIFF\x04\0\0\0ACON".to_vec())
+        .add_raw_resource(ResourceType::Custom(1), 1, vec![0; 4])
+        .add_raw_resource(ResourceType::Custom(6), 1, vec![0; 4])
+        .add_raw_resource(ResourceType::Custom(300), 1, vec![0; 4])
+        .add_typelib(1, vec![0; 4]);
+    let expected = [
+        (id(1), DISCARDABLE | MOVEABLE),
+        (id(3), DISCARDABLE | MOVEABLE),
+        (id(5), DISCARDABLE | MOVEABLE | PURE),
+        (id(6), DISCARDABLE | MOVEABLE | PURE),
+        (id(9), MOVEABLE | PURE),
+        (id(14), DISCARDABLE | MOVEABLE | PURE),
+        (id(16), MOVEABLE | PURE),
+        (id(21), MOVEABLE | PURE),
+        (id(23), MOVEABLE | PURE),
+        (id(24), MOVEABLE | PURE),
+        (id(300), MOVEABLE | PURE),
+        (ResourceName::Name("TYPELIB".to_string()), MOVEABLE | PURE)
+    ];
+    let mut flags: Vec<_> = crate::parse::parse_res(&builder.compile_to_res().data)
+        .unwrap()
+        .into_iter()
+        .skip(1) // the empty leader entry
+        .map(|entry| (entry.ty, entry.memory_flags))
+        .collect();
+    flags.sort_by_key(|(ty, _)| (ty.id().is_none(), ty.id()));
+    assert_eq!(flags, expected);
+}
+
+#[test]
+fn every_version_language_has_its_translation() {
+    use crate::{LanguageId, VersionInfo};
+    let mut german = VersionInfo::default();
+    german
+        .strings
+        .insert("ProductName".to_string(), "Übersetzung".to_string());
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "translation")
+        .add_version_for_language(LanguageId(0x0407), german);
+    let resources = resources(&builder);
+    let versions: Vec<_> = resources.iter().filter(|r| r.ty == id(16)).collect();
+    assert_eq!(versions.len(), 2);
+    let utf16 = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    for version in versions {
+        let key = utf16("Translation\0");
+        let key_start = version
+            .data
+            .windows(key.len())
+            .position(|w| w == key)
+            .expect("the translation is missing");
+        assert_eq!(version.data[key_start - 4..key_start - 2], [8, 0], "wValueLength of the translation");
+        let value = (key_start + key.len()).next_multiple_of(4);
+        let words: Vec<_> = (0..4)
+            .map(|i| u16::from_le_bytes([version.data[value + 2 * i], version.data[value + 2 * i + 1]]))
+            .collect();
+        // both languages are listed, starting with the one of the resource
+        let other = if version.language == 0x0409 { 0x0407 } else { 0x0409 };
+        assert_eq!(words, [version.language, 1200, other, 1200]);
+        // the string table of the same resource uses the same language and code page
+        let table = utf16(&format!("{:04x}04b0", version.language));
+        assert!(version.data.windows(table.len()).any(|w| w == table));
+    }
+    assert!(builder
+        .to_rc_script()
+        .contains("VALUE \"Translation\", 0x0407, 1200, 0x0409, 1200"));
+}
+
+#[test]
+fn archive_member_offsets_point_at_the_members() {
+    let builder = ResourceBuilder::default().add_html(1, b"<p>odd</p>".to_vec());
+    let object = builder.compile_to_coff(TargetType::X86_64);
+    let archive = object.to_archive();
+    assert_eq!(archive.kind, crate::ResourceFileKind::Archive);
+    let data = &archive.data;
+    assert!(data.starts_with(b"!<arch>\n"));
+    // walks the members by their headers: name, size and data of each
+    let mut members = Vec::new();
+    let mut offset = 8;
+    while offset < data.len() {
+        let header = std::str::from_utf8(&data[offset..offset + 60]).unwrap();
+        assert!(header.ends_with("`\n"));
+        let size: usize = header[48..58].trim().parse().unwrap();
+        members.push((offset, header[..16].trim_end().to_string(), &data[offset + 60..offset + 60 + size]));
+        offset = (offset + 60 + size).next_multiple_of(2);
+    }
+    assert_eq!(offset, data.len());
+    let names: Vec<_> = members.iter().map(|(_, name, _)| name.as_str()).collect();
+    assert_eq!(names, ["/", "/", "resources.obj/"]);
+    assert_eq!(members[0].2, 0u32.to_be_bytes());
+    // the second linker member lists the offset of the header of the only object
+    let second = members[1].2;
+    assert_eq!(second[..4], 1u32.to_le_bytes());
+    assert_eq!(u32::from_le_bytes(second[4..8].try_into().unwrap()) as usize, members[2].0);
+    assert_eq!(members[2].2, object.data);
+
+    let obj = temp_dir().join("archive.obj");
+    let lib = temp_dir().join("archive.lib");
+    object.write_to_file(&obj).unwrap();
+    object.write_to_file(&lib).unwrap();
+    assert_eq!(std::fs::read(&obj).unwrap(), object.data);
+    assert_eq!(&std::fs::read(&lib).unwrap(), data);
+    let from_archive = crate::parse::read_resources(data).unwrap().resources;
+    assert_eq!(
+        from_archive,
+        crate::parse::read_resources(&object.data)
+            .unwrap()
+            .resources
+    );
+}
+
+#[test]
+fn package_details_fill_the_strings() {
+    use crate::{first_author, PackageEnv};
+    assert_eq!(first_author("Jane Doe <jane@example.com>:John Doe"), Some("Jane Doe"));
+    assert_eq!(first_author("John Doe"), Some("John Doe"));
+    assert_eq!(first_author("  Crab Corp  <team@crab.corp>"), Some("Crab Corp"));
+    assert_eq!(first_author("<anonymous@example.com>"), None);
+    assert_eq!(first_author(""), None);
+
+    let package = PackageEnv {
+        version: Some("1.0.0".to_string()),
+        name: Some("app".to_string()),
+        description: None,
+        homepage: Some("https://example.com".to_string()),
+        authors: Some("Jane Doe <jane@example.com>:John Doe".to_string()),
+        library: None,
+        source_date_epoch: None
+    };
+    let builder = ResourceBuilder::from_package("1.0.0", "app", None)
+        .unwrap()
+        .set_package_details(&package);
+    assert_eq!(builder.version_strings()["Comments"], "https://example.com");
+    assert_eq!(builder.version_strings()["CompanyName"], "Jane Doe");
+    assert_eq!(builder.version_strings()["FileDescription"], "app");
+    let overridden = builder.add_string("CompanyName", "Crab Corp");
+    assert_eq!(overridden.version_strings()["CompanyName"], "Crab Corp");
+
+    let bare = PackageEnv {
+        homepage: None,
+        authors: None,
+        ..package
+    };
+    let builder = ResourceBuilder::from_package("1.0.0", "app", None)
+        .unwrap()
+        .set_package_details(&bare);
+    assert!(!builder.version_strings().contains_key("Comments"));
+    assert!(!builder.version_strings().contains_key("CompanyName"));
+}
+
+#[test]
+fn manifest_bytes_are_embedded_as_they_are() {
+    let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<assembly><description>Caf\xe9</description></assembly>".to_vec();
+    let embedded = |builder: ResourceBuilder| {
+        let resources = resources(&builder);
+        find(&resources, ResourceType::Manifest, 1).data.clone()
+    };
+    assert_eq!(embedded(ResourceBuilder::default().add_manifest_bytes(latin1.clone())), latin1);
+    let with_bom = [b"\xef\xbb\xbf".as_slice(), b"<assembly/>"].concat();
+    assert_eq!(embedded(ResourceBuilder::default().add_manifest_bytes(with_bom)), b"<assembly/>");
+    // only a UTF-8 byte order mark is removed
+    let utf16_bom = b"\xff\xfe<\0a\0/\0>\0".to_vec();
+    let builder = ResourceBuilder::default().add_manifest_bytes(utf16_bom.clone());
+    assert_eq!(builder.manifest.as_ref().unwrap().1, utf16_bom);
+    // the encoding declaration only has to be UTF-8 for manifests embedded from a string
+    let declared = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><assembly/>";
+    assert!(ResourceBuilder::default()
+        .add_manifest_bytes(declared.as_bytes().to_vec())
+        .try_compile(TargetType::X86_64)
+        .is_ok());
+    assert!(matches!(
+        ResourceBuilder::default()
+            .add_manifest(declared)
+            .try_compile(TargetType::X86_64),
+        Err(crate::EmbedError::InvalidManifest(_))
+    ));
+    assert!(matches!(
+        ResourceBuilder::default()
+            .add_manifest_bytes(b"<assembly><a></assembly>".to_vec())
+            .try_compile(TargetType::X86_64),
+        Err(crate::EmbedError::InvalidManifest(_))
+    ));
+}
+
+#[test]
+fn defaults_can_be_overridden() {
+    let defaults = ResourceBuilder::default()
+        .add_icon_group(1, [Icon::from_png_bytes(png(16, 16, 8, 6)), Icon::from_png_bytes(png(32, 32, 8, 6))])
+        .add_icon(2, Icon::from_png_bytes(png(32, 32, 8, 6)))
+        .add_manifest("<assembly/>")
+        .add_string("CompanyName", "default")
+        .add_string("ProductName", "app");
+
+    let mut builder = defaults.clone();
+    assert!(builder.remove_icon(1));
+    assert!(!builder.remove_icon(1));
+    // the 32px image is still used by the other icon
+    assert_eq!(
+        builder
+            .icons
+            .iter()
+            .map(|(id, _, _)| *id)
+            .collect::<Vec<_>>(),
+        [129]
+    );
+    builder.clear_manifest();
+    assert!(!builder.has_manifest());
+    assert_eq!(builder.remove_string("CompanyName"), Some("default".to_string()));
+    assert_eq!(builder.remove_string("CompanyName"), None);
+
+    let builder = builder
+        .add_icon(1, Icon::from_png_bytes(png(48, 48, 8, 6)))
+        .add_manifest_bytes(b"<assembly></assembly>".to_vec());
+    let resources = resources(&builder);
+    let group = &find(&resources, ResourceType::IconGroup, 1).data;
+    assert_eq!(u16::from_le_bytes([group[4], group[5]]), 1);
+    assert_eq!(u16::from_le_bytes([group[18], group[19]]), 128);
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data, png(48, 48, 8, 6));
+    assert_eq!(find(&resources, ResourceType::Manifest, 1).data, b"<assembly></assembly>");
+    let version = crate::parse::read_version(&compile(&builder, TargetType::X86_64).0)
+        .unwrap()
+        .unwrap();
+    assert!(!version.strings.contains_key("CompanyName"));
+    assert_eq!(version.strings["ProductName"], "app");
+
+    let mut aliased = defaults
+        .add_icon(5, Icon::from_png_bytes(png(64, 64, 8, 6)))
+        .add_icon_alias(6, 5);
+    assert_eq!(aliased.icons.len(), 3);
+    assert!(aliased.remove_icon(5));
+    assert_eq!(aliased.icons.len(), 3);
+    assert!(aliased.remove_icon(6));
+    assert_eq!(aliased.icons.len(), 2);
+}
+
+#[test]
+fn icons_larger_than_256_pixels_are_rejected() {
+    use crate::IconError;
+    assert_eq!(
+        Icon::try_from_png_bytes(png(512, 512, 8, 6)).err(),
+        Some(IconError::TooLarge { width: 512, height: 512 })
+    );
+    assert_eq!(
+        Icon::try_from_png_bytes(png(256, 257, 8, 6)).err(),
+        Some(IconError::TooLarge { width: 256, height: 257 })
+    );
+    assert!(Icon::try_from_png_bytes(png(256, 256, 8, 6)).is_ok());
+    assert!(IconError::TooLarge { width: 512, height: 512 }
+        .to_string()
+        .contains("downscale it first"));
+    assert!(matches!(
+        Icon::try_from_rgba(512, 1, &[0; 2048]),
+        Err(IconError::TooLarge { width: 512, height: 1 })
+    ));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn large_images_are_downscaled() {
+    let img = image::DynamicImage::ImageRgba8(image::RgbaImage::new(512, 256));
+    let icon = Icon::from_dynamic_image(img.clone());
+    assert_eq!((icon.width, icon.height), (256, 128));
+    let icon = Icon::from_dynamic_image_dib(img);
+    assert_eq!((icon.width, icon.height), (256, 128));
+}
+
+#[test]
+fn relocation_ids_match_every_target() {
+    use crate::RelocationType;
+    let expected = [
+        (TargetType::Aarch64, 0x0002, 0x0001),
+        (TargetType::Arm64Ec, 0x0002, 0x0001),
+        (TargetType::I386, 0x0007, 0x0006),
+        (TargetType::X86_64, 0x0003, 0x0002)
+    ];
+    assert_eq!(expected.len(), TARGETS.len());
+    for (target, rva32, addr32) in expected {
+        assert_eq!(RelocationType::Rva32.id(target), rva32, "{target:?}");
+        assert_eq!(RelocationType::Addr32.id(target), addr32, "{target:?}");
+        assert_eq!(RelocationType::from_id(target, rva32), Some(RelocationType::Rva32));
+        assert_eq!(RelocationType::from_id(target, addr32), Some(RelocationType::Addr32));
+        assert_eq!(RelocationType::from_id(target, 0x0000), None);
+
+        let (_, parsed) = compile(&ResourceBuilder::default().add_html(1, vec![1, 2, 3]), target);
+        for relocation in parsed.sections.iter().flat_map(|s| &s.relocations) {
+            assert_eq!(relocation.relocation_type(target), Some(RelocationType::Rva32));
+        }
+    }
+}
+
+#[test]
+fn typelibs_are_stored_under_the_named_type() {
+    let tlb = b"MSFT\x02\x00\x01\x00typelib".to_vec();
+    let builder = ResourceBuilder::default().add_typelib(1, tlb.clone());
+    let resources = resources(&builder);
+    assert_eq!(resources.len(), 1);
+    assert_eq!(resources[0].ty, ResourceName::Name("TYPELIB".to_string()));
+    assert_eq!((&resources[0].name, &resources[0].data), (&id(1), &tlb));
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    assert_eq!((&res[1].ty, &res[1].name, &res[1].data), (&resources[0].ty, &id(1), &tlb));
+}
+
+#[test]
+fn ids_can_be_reused_by_other_types_and_languages() {
+    use crate::LanguageId;
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "ids")
+        .add_html(1, b"<p>".to_vec())
+        .add_raw_resource(ResourceType::Custom(300), 1, vec![1])
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_icon_lang(1, LanguageId::LANG_US, Icon::from_png_bytes(png(32, 32, 8, 6)))
+        .add_dialog(1, crate::DialogTemplate::default())
+        .add_typelib(1, vec![2]);
+    let names: Vec<_> = layout(&builder)
+        .into_iter()
+        .filter(|(_, name, _, _)| *name == id(1))
+        .map(|(ty, _, language, _)| (ty, language))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            (ResourceName::Name("TYPELIB".to_string()), 0x409),
+            (id(5), 0x409),
+            (id(14), 0),
+            (id(14), 0x409),
+            (id(16), 0x409),
+            (id(23), 0x409),
+            (id(300), 0x409)
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Duplicate resource: type 14, id 1, language 0x0409")]
+fn raw_resources_can_not_replace_icons() {
+    let _ = ResourceBuilder::default()
+        .add_icon_lang(1, crate::LanguageId::LANG_US, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_raw_resource(ResourceType::IconGroup, 1, vec![0; 6]);
+}
+
+#[test]
+#[should_panic(expected = "Duplicate resource: type 16, id 1, language 0x0409")]
+fn raw_resources_can_not_replace_the_version_info() {
+    let _ = ResourceBuilder::default()
+        .add_string("ProductName", "twice")
+        .add_raw_resource(ResourceType::Version, 1, vec![0; 4]);
+}
+
+#[test]
+fn duplicates_that_bypass_the_add_methods_are_reported() {
+    let mut builder = ResourceBuilder::default().add_html(1, b"<p>".to_vec());
+    builder
+        .raw_resources
+        .push((ResourceType::Html, 1, b"<p>".to_vec()));
+    assert!(matches!(
+        builder.try_compile(TargetType::X86_64),
+        Err(crate::EmbedError::DuplicateResource { id: 1, .. })
+    ));
+}
+
+/// A complete 2x1 PNG with 8-bit RGB pixels and no alpha channel: a red pixel followed by a blue one.
+const RGB_PNG: &[u8; 70] = include_bytes!("../tests/fixtures/rgb.png");
+
+#[test]
+fn rgb_pngs_are_accepted() {
+    let icon = Icon::try_from_png_bytes(RGB_PNG.to_vec()).unwrap();
+    assert_eq!((icon.width, icon.height, icon.bit_count), (2, 1, 24));
+    let resources = resources(&ResourceBuilder::default().add_icon(1, icon));
+    let group = &find(&resources, ResourceType::IconGroup, 1).data;
+    assert_eq!(group[6..8], [2, 1]);
+    assert_eq!(u16::from_le_bytes([group[12], group[13]]), 24);
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data, RGB_PNG);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn rgb_pngs_have_an_opaque_bmp_fallback() {
+    let dib = Icon::from_png_bytes(RGB_PNG.to_vec()).png_to_dib().unwrap();
+    assert_eq!((dib.width, dib.height, dib.bit_count), (2, 1, 32));
+    // BGRA pixels behind the 40-byte header, followed by an AND mask without transparent pixels
+    assert_eq!(dib.data[40..48], [0, 0, 255, 255, 255, 0, 0, 255]);
+    assert!(dib.data[48..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn icons_from_the_same_bytes_hash_equally() {
+    use std::collections::HashSet;
+    let first = Icon::from_png_bytes(png(16, 16, 8, 6));
+    let second = Icon::from_png_bytes(png(16, 16, 8, 6));
+    let other = Icon::from_png_bytes(png(32, 32, 8, 6));
+    assert_eq!(first.content_hash(), second.content_hash());
+    assert_ne!(first.content_hash(), other.content_hash());
+    // the hash only covers the data, so an icon loaded from a file hashes like one from the same bytes
+    let loaded = Icon::from_png_file(temp_file("hash.png", &png(16, 16, 8, 6))).unwrap();
+    assert_eq!(loaded.content_hash(), first.content_hash());
+    let cache: HashSet<_> = [first, second, other].into_iter().collect();
+    assert_eq!(cache.len(), 2);
+    // DIBs hash by their encoded data as well
+    let dib = Icon::from_rgba(16, 16, &[0; 16 * 16 * 4]);
+    assert_ne!(dib.content_hash(), Icon::from_rgba(16, 16, &[255; 16 * 16 * 4]).content_hash());
+    assert_eq!(dib.content_hash(), Icon::from_rgba(16, 16, &[0; 16 * 16 * 4]).content_hash());
+}
+
+#[test]
+fn dynamic_libraries_get_the_dll_file_type() {
+    use crate::{CrateType, FileType, LibraryKind};
+    const VFT_APP: u32 = 1;
+    const VFT_DLL: u32 = 2;
+    let file_type = |builder: ResourceBuilder| {
+        let builder = builder.add_string("ProductName", "dll");
+        crate::parse::read_version(&compile(&builder, TargetType::X86_64).0)
+            .unwrap()
+            .unwrap()
+            .file_type
+    };
+    assert_eq!(file_type(ResourceBuilder::default()), VFT_APP);
+    assert_eq!(file_type(ResourceBuilder::default().for_dll()), VFT_DLL);
+    assert_eq!(file_type(ResourceBuilder::default().set_library_kind(Some(LibraryKind::Cdylib))), VFT_DLL);
+    assert_eq!(file_type(ResourceBuilder::default().set_library_kind(Some(LibraryKind::Dylib))), VFT_DLL);
+    // a package that also builds binaries keeps describing the executable
+    let both = ResourceBuilder::default()
+        .add_crate_type(CrateType::Bin)
+        .add_crate_type(CrateType::Cdylib);
+    assert_eq!(file_type(both), VFT_APP);
+    let overridden = ResourceBuilder::default()
+        .for_dll()
+        .set_file_type(FileType::Exe);
+    assert_eq!(file_type(overridden), VFT_APP);
+    // the manifest of a library uses the id Windows looks for when loading it
+    let resources = resources(
+        &ResourceBuilder::default()
+            .for_dll()
+            .add_manifest("<assembly/>")
+    );
+    assert_eq!(find(&resources, ResourceType::Manifest, 2).data, b"<assembly/>");
+}
+
+/// A minimal `.ani` file: a RIFF `ACON` header followed by an empty `anih` chunk.
+fn ani_cursor() -> Vec<u8> {
+    let mut data = b"RIFF\0\0\0\0ACONanih".to_vec();
+    data.extend_from_slice(&36u32.to_le_bytes());
+    data.resize(data.len() + 36, 0);
+    let riff_size = (data.len() - 8) as u32;
+    data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    data
+}
+
+#[test]
+fn ani_cursors_are_stored_unchanged() {
+    let resources = resources(&ResourceBuilder::default().add_ani_cursor(1, ani_cursor()));
+    assert_eq!(find(&resources, ResourceType::AniCursor, 1).ty, id(21));
+    assert_eq!(find(&resources, ResourceType::AniCursor, 1).data, ani_cursor());
+}
+
+#[test]
+#[should_panic(expected = "must be an .ani file starting with a RIFF ACON header")]
+fn ani_cursors_need_the_riff_header() {
+    let mut data = ani_cursor();
+    data[8..12].copy_from_slice(b"WAVE");
+    let _ = ResourceBuilder::default().add_ani_cursor(1, data);
+}
+
+#[test]
+#[should_panic(expected = "the RIFF header claims more data than there is")]
+fn ani_cursors_must_not_be_truncated() {
+    let data = ani_cursor();
+    let _ = ResourceBuilder::default().add_ani_cursor(1, data[..data.len() - 1].to_vec());
+}
+
+#[test]
+fn compiled_size_is_exact() {
+    let builders = [
+        ResourceBuilder::default().add_html(1, vec![1, 2, 3]),
+        ResourceBuilder::default()
+            .add_string("ProductName", "size")
+            .add_string("LegalCopyright", "© 2024 🦀")
+            .add_icon_group(1, [Icon::from_png_bytes(png(16, 16, 8, 6)), Icon::from_dib_bytes(dib(32, 32, 4))])
+            .add_manifest("<assembly/>")
+            .add_file("index.html", ResourceType::Html, vec![0; 1001])
+            .add_version_for_language(crate::LanguageId(0x407), crate::VersionInfo::default()),
+        ResourceBuilder::default().add_raw_resource(ResourceType::Custom(300), 7, vec![0; 100_000])
+    ];
+    for builder in &builders {
+        for target in TARGETS {
+            let size = compile(builder, target).0.len();
+            assert_eq!(builder.compiled_size(target).unwrap(), size, "{target:?}");
+            // the estimate only sizes the buffers, but it should be in the right ballpark
+            assert!(
+                (size / 2..size * 2).contains(&builder.estimated_size()),
+                "{} vs {size}",
+                builder.estimated_size()
+            );
+        }
+    }
+}
+
+#[test]
+fn resource_version_goes_into_the_root_directory() {
+    let builder = ResourceBuilder::default().add_html(1, vec![1, 2, 3]);
+    let directory = |builder: &ResourceBuilder| compile(builder, TargetType::X86_64).1.directory.unwrap();
+    let default = directory(&builder);
+    assert_eq!((default.major_version, default.minor_version), (0, 0));
+    let builder = builder.set_resource_version(4, 2);
+    let root = directory(&builder);
+    assert_eq!((root.major_version, root.minor_version), (4, 2));
+    // only the root carries it, the subdirectories and the data entries stay at zero
+    let crate::parse::ResourceDirectoryEntryKind::Directory(types) = &root.entries[0].kind else {
+        panic!("the type entry is not a directory");
+    };
+    assert_eq!((types.major_version, types.minor_version), (0, 0));
+    for target in TARGETS {
+        assert_eq!(compile(&builder, target).1.directory.unwrap(), root);
+    }
+}
+
+#[test]
+fn empty_builders_embed_nothing() {
+    let builder = ResourceBuilder::default();
+    assert!(builder.is_empty());
+    assert!(layout(&builder).is_empty());
+    // a valid object with an empty directory, which the linker accepts as a no-op
+    let (_, parsed) = compile(&builder, TargetType::X86_64);
+    assert!(parsed.directory.unwrap().entries.is_empty());
+    assert!(parsed.sections.iter().all(|s| s.relocations.is_empty()));
+    // the version info is only written if something was set
+    assert!(crate::parse::read_version(&builder.compile(TargetType::X86_64))
+        .unwrap()
+        .is_none());
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    assert_eq!(res.len(), 1, "only the empty leader entry");
+    // finishing doesn't write or link anything
+    let out_dir = temp_dir().join("empty");
+    let written = with_build_env(&out_dir, "msvc", || builder.clone().try_finish_returning()).unwrap();
+    assert!(written.is_empty());
+    assert!(!out_dir.join("resources.lib").exists());
+    // the file type alone is not worth a version info
+    assert!(ResourceBuilder::default()
+        .set_file_type(crate::FileType::Dll)
+        .is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn resource_tree_snapshot() {
+    let builder = ResourceBuilder::default()
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_manifest("<assembly/>")
+        .add_html(7, b"<p>".to_vec())
+        .add_file("index.html", ResourceType::Html, b"<html>".to_vec());
+    let tree = compile(&builder, TargetType::X86_64).1.tree();
+    let entry = |offset: u32, size: u32| serde_json::json!({ "offset": offset, "size": size, "code_page": 0 });
+    let expected = serde_json::json!({
+        "3": { "128": { "0": entry(0, 33) } },
+        "14": { "1": { "0": entry(40, 20) } },
+        "23": {
+            "7": { "1033": entry(64, 3) },
+            "INDEX.HTML": { "1033": entry(72, 6) }
+        },
+        "24": { "1": { "0": entry(80, 11) } }
+    });
+    assert_eq!(serde_json::to_value(&tree).unwrap(), expected);
+    // the same layout for every target, only the relocations differ
+    for target in TARGETS {
+        assert_eq!(compile(&builder, target).1.tree(), tree);
+    }
+}
+
+#[test]
+fn pngs_must_start_with_the_header_chunk() {
+    use crate::IconError;
+    let header = png(16, 16, 8, 6);
+    // an ancillary chunk in front of the header, which would put other bytes at the offsets of the format
+    let mut text_first = header[..8].to_vec();
+    text_first.extend_from_slice(&5u32.to_be_bytes());
+    text_first.extend_from_slice(b"tEXtabcde");
+    text_first.extend_from_slice(&[0; 4]);
+    text_first.extend_from_slice(&header[8..]);
+    assert_eq!(Icon::try_from_png_bytes(text_first).err(), Some(IconError::InvalidPngHeader));
+    let mut long_header = header.clone();
+    long_header[8..12].copy_from_slice(&14u32.to_be_bytes());
+    long_header.insert(29, 0);
+    assert_eq!(Icon::try_from_png_bytes(long_header).err(), Some(IconError::InvalidPngHeader));
+    let mut renamed = header.clone();
+    renamed[12..16].copy_from_slice(b"IHDX");
+    assert_eq!(Icon::try_from_png_bytes(renamed).err(), Some(IconError::InvalidPngHeader));
+    assert!(Icon::try_from_png_bytes(header).is_ok());
+}
+
+#[test]
+fn icon_images_skip_the_ids_of_raw_icons() {
+    let raw = dib(16, 16, 32);
+    let builder = ResourceBuilder::default()
+        .add_raw_resource(ResourceType::Icon, 128, raw.clone())
+        .add_icon_group(1, [Icon::from_png_bytes(png(16, 16, 8, 6)), Icon::from_png_bytes(png(32, 32, 8, 6))]);
+    let resources = resources(&builder);
+    assert_eq!(find(&resources, ResourceType::Icon, 128).data, raw);
+    assert_eq!(find(&resources, ResourceType::Icon, 129).data, png(16, 16, 8, 6));
+    assert_eq!(find(&resources, ResourceType::Icon, 130).data, png(32, 32, 8, 6));
+    let group = &find(&resources, ResourceType::IconGroup, 1).data;
+    assert_eq!(group[18..20], 129u16.to_le_bytes());
+    assert_eq!(group[32..34], 130u16.to_le_bytes());
+
+    // a different base keeps the automatic ids out of the way entirely
+    let moved = ResourceBuilder::default()
+        .set_icon_id_base(1000)
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_raw_resource(ResourceType::Icon, 128, raw);
+    assert_eq!(moved.icon_ids().collect::<Vec<_>>(), [1]);
+    assert_eq!(moved.icons[0].0, 1000);
+}
+
+#[test]
+fn rgba_pixels_become_a_dib_with_a_mask() {
+    #[rustfmt::skip]
+    let rgba = [
+        1, 2, 3, 255,     4, 5, 6, 0,        7, 8, 9, 128,
+        10, 11, 12, 0,    13, 14, 15, 255,   16, 17, 18, 255
+    ];
+    let icon = Icon::from_rgba(3, 2, &rgba);
+    let mut expected = Vec::new();
+    for field in [40u32, 3, 4] {
+        expected.extend_from_slice(&field.to_le_bytes());
+    }
+    expected.extend_from_slice(&[1, 0, 32, 0]); // planes and bit count
+    expected.extend_from_slice(&[0; 4]);
+    expected.extend_from_slice(&32u32.to_le_bytes()); // 24 bytes of color and two padded mask rows
+    expected.extend_from_slice(&[0; 16]);
+    #[rustfmt::skip]
+    expected.extend_from_slice(&[
+        // BGRA, bottom-up
+        12, 11, 10, 0,    15, 14, 13, 255,   18, 17, 16, 255,
+        3, 2, 1, 255,     6, 5, 4, 0,        9, 8, 7, 128,
+        // the mask, bottom-up with every row padded to four bytes, only fully transparent pixels are masked
+        0x80, 0, 0, 0,
+        0x40, 0, 0, 0
+    ]);
+    assert_eq!(icon.data, expected);
+    assert_eq!((icon.width, icon.height, icon.bit_count), (3, 2, 32));
+    assert_eq!(
+        Icon::try_from_rgba(3, 2, &rgba[4..]).err(),
+        Some(crate::IconError::PixelCountMismatch { expected: 24, actual: 20 })
+    );
+    // the DIB is a regular icon image that the group describes like one from an .ico file
+    let resources = resources(&ResourceBuilder::default().add_icon(1, icon));
+    let group = &find(&resources, ResourceType::IconGroup, 1).data;
+    assert_eq!(group[6..8], [3, 2]);
+    assert_eq!(u16::from_le_bytes([group[12], group[13]]), 32);
+    assert_eq!(u32::from_le_bytes(group[14..18].try_into().unwrap()) as usize, expected.len());
+}
+
+#[test]
+fn packaged_apps_can_override_the_fixed_info() {
+    use crate::{FileOs, FileType, ManifestBuilder};
+    let manifest = ManifestBuilder::new().package_identity("CN=Crab & Co", "Crab.App", "App");
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "packaged")
+        .set_file_os(FileOs::Nt)
+        .set_file_type(FileType::Exe)
+        .set_file_subtype(0)
+        .add_generated_manifest(manifest);
+    let bytes = builder.try_compile(TargetType::X86_64).unwrap();
+    let resources = crate::parse::read_resources(&bytes).unwrap();
+    let version = resources.version.unwrap();
+    assert_eq!((version.file_os, version.file_type, version.file_subtype), (0x0004_0000, 1, 0));
+    let manifest = resources.manifest.unwrap();
+    assert!(manifest.contains(
+        "<msix xmlns=\"urn:schemas-microsoft-com:msix.v1\" publisher=\"CN=Crab &amp; Co\" packageName=\"Crab.App\" applicationId=\"App\"/>"
+    ));
+    // desktop builds keep VOS_NT_WINDOWS32
+    let desktop = crate::parse::read_version(
+        &ResourceBuilder::default()
+            .add_string("ProductName", "desktop")
+            .compile(TargetType::X86_64)
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!((desktop.file_os, desktop.file_type), (0x0004_0004, 1));
+}
+
+#[test]
+fn target_env_only_changes_the_files() {
+    use crate::TargetEnv;
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "env")
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .split_objects(true);
+    let out_dir = temp_dir().join("env");
+    let finish = |target_env: &str, builder: ResourceBuilder| {
+        with_build_env(&out_dir, target_env, || builder.finish_returning())
+            .into_iter()
+            .map(|(path, file)| (path.file_name().unwrap().to_string_lossy().into_owned(), file.data))
+            .collect::<Vec<_>>()
+    };
+    // MSVC can only link one object, GNU gets one per group with a single section each
+    let msvc = finish("msvc", builder.clone());
+    assert_eq!(msvc, [("resources.lib".to_string(), builder.compile(TargetType::X86_64))]);
+    let gnu = finish("gnu", builder.clone());
+    let split: Vec<_> = builder
+        .compile_split(TargetType::X86_64)
+        .into_iter()
+        .map(|(group, data)| (format!("resources-{group}.o"), data))
+        .collect();
+    assert_eq!(gnu, split);
+    // an explicit target env wins over the environment
+    assert_eq!(finish("msvc", builder.clone().set_target_env(TargetEnv::Gnu)), gnu);
+    for (_, data) in &gnu {
+        let parsed = parse_coff(data).unwrap();
+        assert_eq!(parsed.sections.len(), 1);
+        assert_eq!(parsed.sections[0].characteristics, 0x4000_0040);
+        // static symbols and no COMDAT selection, like the objects of cvtres and windres
+        assert!(parsed.symbols.iter().all(|s| s.storage_class == 3));
+        assert!(parsed
+            .symbols
+            .iter()
+            .filter_map(|s| s.section_aux.as_ref())
+            .all(|aux| aux.selection == 0));
+    }
+}
+
+#[test]
+fn icons_and_manifests_are_language_neutral() {
+    use crate::LanguageId;
+    let builder = ResourceBuilder::default()
+        .add_string("ProductName", "neutral")
+        .add_icon(1, Icon::from_png_bytes(png(16, 16, 8, 6)))
+        .add_manifest("<assembly/>")
+        .add_icon_lang(2, LanguageId(0x0407), Icon::from_png_bytes(png(32, 32, 8, 6)));
+    let languages: Vec<_> = layout(&builder)
+        .into_iter()
+        .map(|(ty, name, language, _)| (ty, name, language))
+        .collect();
+    assert_eq!(
+        languages,
+        [
+            (id(3), id(128), 0x0000),
+            (id(3), id(129), 0x0407),
+            (id(14), id(1), 0x0000),
+            (id(14), id(2), 0x0407),
+            (id(16), id(1), 0x0409),
+            (id(24), id(1), 0x0000)
+        ]
+    );
+    assert_eq!(LanguageId::NEUTRAL, LanguageId(0));
+}
+
+#[test]
+fn raw_resource_headers_round_trip() {
+    use crate::{LanguageId, RawResource};
+    let resource = RawResource {
+        type_id: 0x1234,
+        id: 9,
+        language: LanguageId(0x0407),
+        memory_flags: 0x0050,
+        data_version: 3,
+        version: 0x0102_0304,
+        characteristics: 0xdead_beef,
+        data: b"full header".to_vec()
+    };
+    let builder = ResourceBuilder::default().add_raw_resource_full(resource.clone());
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    let entry = &res[1];
+    assert_eq!((&entry.ty, &entry.name, entry.language), (&id(0x1234), &id(9), 0x0407));
+    assert_eq!((entry.memory_flags, entry.data_version), (0x0050, 3));
+    assert_eq!((entry.version, entry.characteristics), (0x0102_0304, 0xdead_beef));
+    assert_eq!(entry.data, resource.data);
+    // objects only keep the type, name, language and data
+    assert_eq!(layout(&builder), [(id(0x1234), id(9), 0x0407, 11)]);
+
+    // the defaults are the header rc.exe writes
+    let default = RawResource::new(0x1234, 9, vec![]);
+    assert_eq!(default.language, LanguageId::LANG_US);
+    assert_eq!(
+        (default.memory_flags, default.data_version, default.version, default.characteristics),
+        (0x0030, 0, 0, 0)
+    );
+    let plain = ResourceBuilder::default().add_raw_resource(ResourceType::Custom(0x1234), 9, b"full header".to_vec());
+    let plain = crate::parse::parse_res(&plain.compile_to_res().data).unwrap();
+    let full = ResourceBuilder::default().add_raw_resource_full(RawResource::new(0x1234, 9, b"full header".to_vec()));
+    assert_eq!(crate::parse::parse_res(&full.compile_to_res().data).unwrap(), plain);
+}
+
+#[test]
+fn merged_res_files_have_a_single_empty_leader() {
+    let first = ResourceBuilder::default()
+        .add_string("ProductName", "merged")
+        .add_html(1, b"<p>".to_vec())
+        .compile_to_res()
+        .data;
+    let second = ResourceBuilder::default()
+        .add_html(2, b"<b>".to_vec())
+        .add_file("index.html", ResourceType::Html, b"<i>".to_vec())
+        .compile_to_res()
+        .data;
+    let merged = ResourceBuilder::default()
+        .add_res_file(first)
+        .add_res_file(second)
+        .compile_to_res()
+        .data;
+    let entries = crate::parse::parse_res(&merged).unwrap();
+    let leaders: Vec<_> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.ty == id(0))
+        .map(|(i, entry)| (i, entry.data.len()))
+        .collect();
+    assert_eq!(leaders, [(0, 0)]);
+    let mut names: Vec<_> = entries[1..]
+        .iter()
+        .map(|entry| (entry.ty.clone(), entry.name.clone()))
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            (id(16), id(1)),
+            (id(23), id(1)),
+            (id(23), id(2)),
+            (id(23), ResourceName::Name("INDEX.HTML".to_string()))
+        ]
+    );
+}
+
+#[test]
+fn manifests_can_use_several_ids() {
+    let builder = ResourceBuilder::default()
+        .add_manifest("<assembly>app</assembly>")
+        .add_activation_manifest(2, "<assembly>isolation</assembly>")
+        .add_activation_manifest(3, "<assembly>plugin</assembly>");
+    let resources = resources(&builder);
+    let manifests: Vec<_> = resources
+        .iter()
+        .filter(|r| r.ty == id(24))
+        .map(|r| (r.name.clone(), r.language, String::from_utf8(r.data.clone()).unwrap()))
+        .collect();
+    assert_eq!(
+        manifests,
+        [
+            (id(1), 0, "<assembly>app</assembly>".to_string()),
+            (id(2), 0, "<assembly>isolation</assembly>".to_string()),
+            (id(3), 0, "<assembly>plugin</assembly>".to_string())
+        ]
+    );
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    assert_eq!(res.iter().filter(|entry| entry.ty == id(24)).count(), 3);
+
+    // every manifest is checked on its own
+    let broken = ResourceBuilder::default()
+        .add_manifest("<assembly/>")
+        .add_activation_manifest(3, "<assembly>");
+    assert!(matches!(
+        broken.try_compile(TargetType::X86_64),
+        Err(crate::EmbedError::InvalidManifest(_))
+    ));
+    assert!(broken.try_compile_split(TargetType::X86_64).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Duplicate resource: type 24, id 1")]
+fn activation_manifests_can_not_replace_the_application_manifest() {
+    let _ = ResourceBuilder::default()
+        .add_manifest("<assembly/>")
+        .add_activation_manifest(1, "<assembly/>");
+}
+
+#[test]
+fn icon_files_are_loaded_from_fixtures() {
+    use std::io::ErrorKind;
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let icon = Icon::from_png_file(fixtures.join("rgb.png")).unwrap();
+    assert_eq!(icon.dimensions(), (2, 1));
+    assert!(icon.source == Some(crate::IconSource::Png(fixtures.join("rgb.png"))));
+    let from_bytes = Icon::from_png_bytes(RGB_PNG.to_vec());
+    assert_eq!(icon.content_hash(), from_bytes.content_hash());
+
+    let ico = temp_file("fixture.ico", &ico(&[RGB_PNG.to_vec(), dib(16, 16, 32)]));
+    let icons = Icon::from_ico_file(&ico).unwrap();
+    assert_eq!(icons.len(), 2);
+    assert_eq!(icons[0].data, RGB_PNG);
+    assert!(icons[1].source == Some(crate::IconSource::Ico { ico: ico.clone(), index: 1 }));
+
+    // missing files keep their kind, unsupported ones wrap the icon error
+    let missing = Icon::from_png_file(fixtures.join("missing.png"))
+        .err()
+        .unwrap();
+    assert_eq!(missing.kind(), ErrorKind::NotFound);
+    let invalid = Icon::from_png_file(temp_file("invalid.png", b"not a png"))
+        .err()
+        .unwrap();
+    assert_eq!(invalid.kind(), ErrorKind::InvalidData);
+    let inner = invalid
+        .into_inner()
+        .unwrap()
+        .downcast::<crate::IconError>()
+        .unwrap();
+    assert_eq!(*inner, crate::IconError::InvalidPng);
+    assert_eq!(
+        Icon::from_ico_file(temp_file("invalid.ico", b""))
+            .err()
+            .unwrap()
+            .kind(),
+        ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn custom_resource_types_round_trip() {
+    let ty = ResourceType::Custom(0x1234);
+    assert_eq!(ResourceType::from_id(0x1234), ty);
+    assert_eq!((ty.id(), u32::from(ty), ty.name()), (0x1234, 0x1234, None));
+    assert_eq!(ty.to_string(), "4660");
+    // standard ids map to their variants, and custom types with the same id are the same type
+    assert!(matches!(ResourceType::from_id(0x18), ResourceType::Manifest));
+    assert_eq!(ResourceType::Custom(0x18), ResourceType::Manifest);
+    assert_eq!(ResourceType::TypeLib.to_string(), "TYPELIB");
+    assert!(ResourceType::TypeLib < ResourceType::Custom(1));
+
+    let builder = ResourceBuilder::default().add_raw_resource(ty, 3, b"custom".to_vec());
+    let resources = resources(&builder);
+    assert_eq!(find(&resources, ty, 3).data, b"custom");
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    assert_eq!((&res[1].ty, &res[1].name, &res[1].data[..]), (&id(0x1234), &id(3), &b"custom"[..]));
+}
+
+#[test]
+fn unwritable_output_paths_are_reported() {
+    use crate::EmbedError;
+    let builder = ResourceBuilder::default().add_html(1, b"<p>".to_vec());
+    // a file where a directory is expected can't be created or written to, even by root
+    let file = temp_file("not-a-directory", b"");
+    let err = with_build_env(&file, "msvc", || builder.clone().try_finish()).unwrap_err();
+    let EmbedError::Io(io) = &err else {
+        panic!("unexpected error {err}");
+    };
+    let path = file.join("resources.lib");
+    assert!(io.to_string().starts_with(&path.display().to_string()), "{io}");
+    assert!(err.to_string().contains(&path.display().to_string()));
+    let nested = file.join("nested").join("resources.obj");
+    let err = builder
+        .clone()
+        .finish_to(&nested, TargetType::X86_64)
+        .unwrap_err();
+    assert!(matches!(&err, EmbedError::Io(_)));
+    assert!(err.to_string().contains(&nested.display().to_string()), "{err}");
+
+    // parent directories that don't exist yet are created
+    let out_dir = temp_dir().join("missing").join("out");
+    with_build_env(&out_dir, "msvc", || builder.clone().try_finish()).unwrap();
+    assert!(out_dir.join("resources.lib").is_file());
+
+    let _lock = ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    assert!(matches!(builder.try_finish(), Err(EmbedError::MissingEnvVar("CARGO_CFG_TARGET_ARCH"))));
+}
+
+#[test]
+fn files_are_found_by_their_name() {
+    let builder = ResourceBuilder::default()
+        .add_file("index.html", ResourceType::Html, b"<html>".to_vec())
+        .add_file("app.js", ResourceType::Html, b"main()".to_vec())
+        .add_html(1, b"<p>".to_vec());
+    let (_, parsed) = compile(&builder, TargetType::X86_64);
+    // named entries come first in the directory, sorted by name
+    let html = parsed
+        .directory
+        .as_ref()
+        .unwrap()
+        .entries
+        .iter()
+        .find(|entry| entry.name == id(23))
+        .unwrap();
+    let crate::parse::ResourceDirectoryEntryKind::Directory(names) = &html.kind else {
+        panic!("the type entry is not a directory");
+    };
+    let names: Vec<_> = names
+        .entries
+        .iter()
+        .map(|entry| entry.name.clone())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ResourceName::Name("APP.JS".to_string()),
+            ResourceName::Name("INDEX.HTML".to_string()),
+            id(1)
+        ]
+    );
+    let resources = parsed.resources();
+    let index = resources
+        .iter()
+        .find(|r| r.name == ResourceName::Name("INDEX.HTML".to_string()))
+        .unwrap();
+    assert_eq!((&index.ty, index.language, &index.data[..]), (&id(23), 0x409, &b"<html>"[..]));
+    let res = crate::parse::parse_res(&builder.compile_to_res().data).unwrap();
+    assert!(res
+        .iter()
+        .any(|entry| entry.name == ResourceName::Name("APP.JS".to_string()) && entry.data == b"main()"));
+}
+
+#[test]
+#[should_panic(expected = "Invalid resource name \"#1\"")]
+fn file_names_can_not_look_like_ids() {
+    let _ = ResourceBuilder::default().add_file("#1", ResourceType::Html, vec![]);
+}
+
+#[test]
+fn fingerprints_ignore_the_timestamps() {
+    use crate::Version;
+    let builder = ResourceBuilder::default()
+        .set_file_version(Version::new(1, 2, 3, 4))
+        .add_string("ProductName", "Crab")
+        .add_raw_resource(ResourceType::Custom(10), 1, vec![1, 2, 3]);
+    let fingerprint = builder.content_fingerprint(TargetType::X86_64).unwrap();
+    for target in TARGETS {
+        assert_eq!(builder.content_fingerprint(target).unwrap(), fingerprint, "{target:?}");
+    }
+    assert_eq!(
+        fingerprint,
+        builder
+            .clone()
+            .set_version_timestamp(0x01d9_0000_0000_0000)
+            .content_fingerprint(TargetType::X86_64)
+            .unwrap()
+    );
+
+    let changed = [
+        builder
+            .clone()
+            .add_raw_resource(ResourceType::Custom(10), 2, vec![1, 2, 3]),
+        builder.clone().add_string("ProductName", "Lobster"),
+        builder.clone().set_file_version(Version::new(1, 2, 3, 5)),
+        ResourceBuilder::default()
+            .set_file_version(Version::new(1, 2, 3, 4))
+            .add_string("ProductName", "Crab")
+            .add_raw_resource(ResourceType::Custom(10), 1, vec![1, 2, 4])
+    ];
+    for builder in changed {
+        assert_ne!(builder.content_fingerprint(TargetType::X86_64).unwrap(), fingerprint);
+    }
+}
+
+#[test]
+fn versions_round_trip_through_the_parser() {
+    use crate::{FileFlag, Version};
+    let builder = ResourceBuilder::default()
+        .set_file_version(Version::new(1, 2, 3, 4))
+        .set_product_version(Version::new(5, 6, 7, 8))
+        .set_version_timestamp(0x0123_4567_89ab_cdef)
+        .add_file_flag(FileFlag::Prerelease)
+        .add_string("ProductName", "Crab")
+        .add_string("CompanyName", "Crab Corp");
+    let version = crate::parse::read_version(&compile(&builder, TargetType::X86_64).0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(version.file_version, Version::new(1, 2, 3, 4));
+    assert_eq!(version.product_version, Version::new(5, 6, 7, 8));
+    assert_eq!(version.file_date, 0x0123_4567_89ab_cdef);
+    assert_eq!((version.flags_mask, version.flags), (0x3f, 0x02));
+    assert_eq!(version.strings["ProductName"], "Crab");
+    assert_eq!(version.strings["CompanyName"], "Crab Corp");
+    assert_eq!(
+        crate::parse::read_version(&compile(&ResourceBuilder::default(), TargetType::X86_64).0).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn versions_are_parsed_from_strings() {
+    use crate::{ParseVersionError, Version};
+    assert_eq!("1".parse(), Ok(Version::new(1, 0, 0, 0)));
+    assert_eq!("1.2".parse(), Ok(Version::new(1, 2, 0, 0)));
+    assert_eq!("1.2.3.4".parse(), Ok(Version::new(1, 2, 3, 4)));
+    assert_eq!("1.2.3.4.5".parse::<Version>(), Err(ParseVersionError::TooManyComponents));
+    assert!(matches!(
+        "1.x".parse::<Version>(),
+        Err(ParseVersionError::InvalidComponent { index: 1, component, .. }) if component == "x"
+    ));
+    assert!(matches!(
+        "1.65536".parse::<Version>(),
+        Err(ParseVersionError::InvalidComponent { index: 1, .. })
+    ));
+}
+
+/// A COFF object whose only section is a `.rsrc$01` holding `table`.
+fn object_with_resource_table(table: &[u8]) -> Vec<u8> {
+    const HEADERS_SIZE: u32 = 20 + 40;
+    let mut object = Vec::new();
+    object.extend_from_slice(&0x8664u16.to_le_bytes());
+    object.extend_from_slice(&1u16.to_le_bytes());
+    object.extend_from_slice(&[0; 16]);
+    object.extend_from_slice(b".rsrc$01");
+    object.extend_from_slice(&[0; 8]);
+    object.extend_from_slice(&(table.len() as u32).to_le_bytes());
+    object.extend_from_slice(&HEADERS_SIZE.to_le_bytes());
+    object.extend_from_slice(&[0; 12]);
+    object.extend_from_slice(&0x4000_0040u32.to_le_bytes());
+    object.extend_from_slice(table);
+    object
+}
+
+/// A chain of `levels` directories that each point `entries` times at the next one.
+fn directory_chain(levels: usize, entries: u16) -> Vec<u8> {
+    let directory_size = 16 + 8 * entries as usize;
+    let mut table = Vec::with_capacity(levels * directory_size);
+    for level in 0..levels {
+        table.extend_from_slice(&[0; 12]);
+        table.extend_from_slice(&0u16.to_le_bytes());
+        table.extend_from_slice(&entries.to_le_bytes());
+        let next = ((level + 1) * directory_size) as u32 | 0x8000_0000;
+        for id in 0..entries as u32 {
+            table.extend_from_slice(&(id + 1).to_le_bytes());
+            table.extend_from_slice(&next.to_le_bytes());
+        }
+    }
+    table
+}
+
+#[test]
+fn deep_resource_directories_are_rejected() {
+    use crate::parse::ParseError;
+    let chain = object_with_resource_table(&directory_chain(200_000, 1));
+    assert_eq!(
+        parse_coff(&chain).err(),
+        Some(ParseError::Invalid("resource directory is nested deeper than type, name and language"))
+    );
+}
+
+#[test]
+fn shared_resource_directories_are_rejected() {
+    use crate::parse::ParseError;
+    // without the checks, every level would double the work
+    let doubling = object_with_resource_table(&directory_chain(40, 2));
+    assert!(parse_coff(&doubling).is_err());
+    // two types that share an empty name directory
+    let mut shared = directory_chain(2, 2);
+    shared[46..48].copy_from_slice(&0u16.to_le_bytes());
+    assert_eq!(
+        parse_coff(&object_with_resource_table(&shared)).err(),
+        Some(ParseError::Invalid("resource directory is referenced more than once"))
+    );
+    // a subdirectory pointing back at the root
+    let mut cycle = directory_chain(1, 1);
+    cycle[20..24].copy_from_slice(&0x8000_0000u32.to_le_bytes());
+    assert_eq!(
+        parse_coff(&object_with_resource_table(&cycle)).err(),
+        Some(ParseError::Invalid("resource directory is referenced more than once"))
+    );
+}
+
+#[test]
+fn file_flags_can_be_given_as_collections() {
+    use crate::FileFlag;
+    let expected = ResourceBuilder::default().add_file_flags(FileFlag::Debug | FileFlag::Prerelease);
+    let builders = [
+        ResourceBuilder::default().add_file_flags([FileFlag::Debug, FileFlag::Prerelease]),
+        ResourceBuilder::default().add_file_flags(vec![FileFlag::Prerelease, FileFlag::Debug]),
+        ResourceBuilder::default().add_file_flags(std::collections::BTreeSet::from([FileFlag::Debug, FileFlag::Prerelease]))
+    ];
+    for builder in builders {
+        assert_eq!(builder.file_flags(), expected.file_flags());
+    }
+    assert!(ResourceBuilder::default()
+        .add_file_flags([])
+        .file_flags()
+        .is_empty());
+}